@@ -0,0 +1,405 @@
+///
+/// Compiler diagnostics with source snippets
+///
+use std::path::Path;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::parser2::{Location, ParseError, Span};
+
+const STYLE_ERROR: &str = "\x1B[1;31m";
+const STYLE_WARNING: &str = "\x1B[1;33m";
+const STYLE_NOTE: &str = "\x1B[1;32m";
+const STYLE_GUTTER: &str = "\x1B[1;34m";
+const STYLE_BOLD: &str = "\x1B[1m";
+const STYLE_RESET: &str = "\x1B[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+	Error,
+	Warning,
+	Note,
+}
+impl Level {
+	fn name(self) -> &'static str {
+		match self {
+			Self::Error => "error",
+			Self::Warning => "warning",
+			Self::Note => "note",
+		}
+	}
+	fn style(self) -> &'static str {
+		match self {
+			Self::Error => STYLE_ERROR,
+			Self::Warning => STYLE_WARNING,
+			Self::Note => STYLE_NOTE,
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+	pub level: Level,
+	pub code: &'static str,
+	pub message: String,
+	pub span: Option<Span>,
+	pub label: Option<String>,
+	pub notes: Vec<String>,
+	pub help: Option<String>,
+}
+impl Diagnostic {
+	pub fn new(level: Level, code: &'static str, message: impl Into<String>) -> Self {
+		Self {
+			level,
+			code,
+			message: message.into(),
+			span: None,
+			label: None,
+			notes: Vec::new(),
+			help: None,
+		}
+	}
+	pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+		Self::new(Level::Error, code, message)
+	}
+	pub fn with_span(mut self, span: Span) -> Self {
+		self.span = Some(span);
+		self
+	}
+	pub fn with_location(self, location: Location) -> Self {
+		self.with_span(Span::new(location, location))
+	}
+	pub fn with_label(mut self, label: impl Into<String>) -> Self {
+		self.label = Some(label.into());
+		self
+	}
+	pub fn with_note(mut self, note: impl Into<String>) -> Self {
+		self.notes.push(note.into());
+		self
+	}
+	pub fn with_help(mut self, help: impl Into<String>) -> Self {
+		self.help = Some(help.into());
+		self
+	}
+
+	///
+	/// Render the diagnostic with the offending source line and a caret underline
+	///
+	pub fn render(&self, source: &str, path: &Path, color: bool) -> String {
+		let style = |style: &'static str| if color { style } else { "" };
+		let mut result = format!(
+			"{}{}[{}]{}{}: {}{}\n",
+			style(self.level.style()),
+			self.level.name(),
+			self.code,
+			style(STYLE_RESET),
+			style(STYLE_BOLD),
+			self.message,
+			style(STYLE_RESET),
+		);
+		let span = match self.span {
+			Some(span) => span,
+			None => {
+				result.push_str(&format!(
+					" {}-->{} {}\n",
+					style(STYLE_GUTTER),
+					style(STYLE_RESET),
+					path.display()
+				));
+				self.render_footer(&mut result, 0, color);
+				return result;
+			}
+		};
+		let start = span.start.offset.min(source.len());
+		let line_start = source[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+		let line_end = source[start..]
+			.find('\n')
+			.map(|i| start + i)
+			.unwrap_or_else(|| source.len());
+		let line = source[line_start..line_end].trim_end_matches('\r');
+		let number = format!("{}", span.start.line + 1);
+		let gutter = " ".repeat(number.len());
+
+		// keep tabs in the padding so the caret lines up with the source line
+		let padding = source[line_start..start]
+			.graphemes(true)
+			.map(|g| if g == "\t" { "\t" } else { " " })
+			.collect::<String>();
+		let end = span.end.offset.max(start).min(line_start + line.len());
+		let width = source[start..end].graphemes(true).count().max(1);
+
+		result.push_str(&format!(
+			"{}{}-->{} {}:{}:{}\n",
+			gutter,
+			style(STYLE_GUTTER),
+			style(STYLE_RESET),
+			path.display(),
+			span.start.line + 1,
+			span.start.column + 1,
+		));
+		result.push_str(&format!(
+			"{} {}|{}\n",
+			gutter,
+			style(STYLE_GUTTER),
+			style(STYLE_RESET)
+		));
+		result.push_str(&format!(
+			"{}{} |{} {}\n",
+			style(STYLE_GUTTER),
+			number,
+			style(STYLE_RESET),
+			line
+		));
+		result.push_str(&format!(
+			"{} {}|{} {}{}{}{}{}\n",
+			gutter,
+			style(STYLE_GUTTER),
+			style(STYLE_RESET),
+			padding,
+			style(self.level.style()),
+			"^".repeat(width),
+			self.label
+				.as_ref()
+				.map(|l| format!(" {}", l))
+				.unwrap_or_default(),
+			style(STYLE_RESET),
+		));
+		self.render_footer(&mut result, gutter.len(), color);
+		result
+	}
+	fn render_footer(&self, result: &mut String, indent: usize, color: bool) {
+		let style = |style: &'static str| if color { style } else { "" };
+		let gutter = " ".repeat(indent);
+		if !self.notes.is_empty() || self.help.is_some() {
+			result.push_str(&format!(
+				"{} {}|{}\n",
+				gutter,
+				style(STYLE_GUTTER),
+				style(STYLE_RESET)
+			));
+		}
+		for note in &self.notes {
+			result.push_str(&format!(
+				"{} {}={} {}note{}: {}\n",
+				gutter,
+				style(STYLE_GUTTER),
+				style(STYLE_RESET),
+				style(STYLE_BOLD),
+				style(STYLE_RESET),
+				note
+			));
+		}
+		if let Some(help) = &self.help {
+			result.push_str(&format!(
+				"{} {}={} {}help{}: {}\n",
+				gutter,
+				style(STYLE_GUTTER),
+				style(STYLE_RESET),
+				style(STYLE_BOLD),
+				style(STYLE_RESET),
+				help
+			));
+		}
+	}
+}
+
+impl From<&ParseError> for Diagnostic {
+	fn from(error: &ParseError) -> Self {
+		match error {
+			ParseError::Unspecified => Self::error("E0000", "unspecified parse error")
+				.with_note("the parser failed without further information"),
+			ParseError::UnexpectedEOF { context, expected } => {
+				Self::error("E0001", "unexpected end of file")
+					.with_location(context.location())
+					.with_label(format!("expected {}", expected))
+					.with_help("check whether the file was truncated")
+			}
+			ParseError::UnexpectedCharacter {
+				context,
+				expected,
+				found,
+			} => Self::error(
+				"E0002",
+				format!("unexpected character `{}`", found.escape_debug()),
+			)
+			.with_location(context.location())
+			.with_label(format!("expected {}", expected)),
+			ParseError::UnexpectedToken {
+				context,
+				expected,
+				found,
+			} => Self::error("E0004", format!("unexpected `{}`", found.escape_debug()))
+				.with_location(context.location())
+				.with_label(format!("expected {}", expected)),
+			ParseError::InvalidLiteral {
+				context,
+				literal,
+				reason,
+			} => Self::error("E0005", format!("invalid literal `{}`", literal))
+				.with_location(context.location())
+				.with_label(reason.clone()),
+			ParseError::LiteralOverflow {
+				context,
+				literal,
+				bounds,
+			} => Self::error("E0006", format!("literal `{}` out of range", literal))
+				.with_location(context.location())
+				.with_label(format!("does not fit into {}", bounds)),
+			ParseError::InvalidDeclaration {
+				context,
+				declaration,
+				reason,
+			} => Self::error("E0007", format!("invalid `{}` declaration", declaration))
+				.with_location(context.location())
+				.with_label(reason.clone()),
+			ParseError::NonAssociative { context, operator } => Self::error(
+				"E0008",
+				format!("operator `{}` is not associative", operator),
+			)
+			.with_location(context.location())
+			.with_label("cannot be chained")
+			.with_help("use `[...]` to group the operands"),
+			ParseError::ModuleError {
+				context,
+				module,
+				reason,
+			} => Self::error("E0009", format!("cannot load module `{}`", module))
+				.with_location(context.location())
+				.with_label(reason.clone()),
+			ParseError::InvalidTag {
+				context,
+				tag,
+				reason,
+			} => Self::error("E0010", format!("invalid tag `@{}`", tag))
+				.with_location(context.location())
+				.with_label(reason.clone()),
+			ParseError::MisplacedCompileTime { context, reason } => {
+				Self::error("E0011", "misplaced compile-time evaluation `!`")
+					.with_location(context.location())
+					.with_label(reason.clone())
+					.with_help(
+						"`!` evaluates an expression, a binding value or a `fn` at compile time",
+					)
+			}
+			ParseError::InvalidPrefix {
+				context,
+				prefix,
+				reason,
+			} => Self::error("E0012", format!("invalid `{}` prefix", prefix))
+				.with_location(context.location())
+				.with_label(reason.clone()),
+			ParseError::InconsistentIndentation {
+				context,
+				expected,
+				found,
+			} => Self::error("E0013", "inconsistent indentation")
+				.with_location(context.location())
+				.with_label(format!("indented with {}", found))
+				.with_note(format!("the file is indented with {}", expected))
+				.with_help(
+					"indent each file with either tabs or the same number of spaces per level",
+				),
+			ParseError::NoneMatched { context } => Self::error("E0003", "no alternative matched")
+				.with_location(context.location())
+				.with_note("none of the possible forms could be parsed at this position"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::parser2::InputContext;
+	use super::*;
+
+	fn span(source: &str, text: &str) -> Span {
+		let offset = source.find(text).unwrap();
+		let location = |offset: usize| {
+			let before = &source[..offset];
+			let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+			Location {
+				offset,
+				line: before.matches('\n').count(),
+				column: before[line_start..].graphemes(true).count(),
+			}
+		};
+		Span::new(location(offset), location(offset + text.len()))
+	}
+
+	#[test]
+	fn snippets() {
+		let source = "a := 1\nb := ]\nc\n";
+		let diagnostic = Diagnostic::error("E0004", "unexpected `]`")
+			.with_span(span(source, "]"))
+			.with_label("expected a value");
+		assert_eq!(
+			diagnostic.render(source, Path::new("test.ca"), false),
+			"error[E0004]: unexpected `]`\n --> test.ca:2:6\n  |\n2 | b := ]\n  |      ^ expected a value\n"
+		);
+	}
+
+	#[test]
+	fn carets() {
+		let source = "x\n\tfoo bar\n";
+		let diagnostic = Diagnostic::error("E0001", "message").with_span(span(source, "bar"));
+		let rendered = diagnostic.render(source, Path::new("test.ca"), false);
+		let lines = rendered.lines().collect::<Vec<_>>();
+		assert_eq!(lines[3], "2 | \tfoo bar");
+		assert_eq!(lines[4], "  | \t    ^^^");
+
+		let span = Span::new(span(source, "foo").start, span(source, "x").start);
+		let diagnostic = Diagnostic::error("E0001", "message").with_span(span);
+		let rendered = diagnostic.render(source, Path::new("test.ca"), false);
+		assert_eq!(rendered.lines().nth(4), Some("  | \t^"));
+	}
+
+	#[test]
+	fn colors() {
+		let source = "a := ]\n";
+		let diagnostic = Diagnostic::error("E0004", "unexpected `]`")
+			.with_span(span(source, "]"))
+			.with_note("a note")
+			.with_help("some help");
+		let plain = diagnostic.render(source, Path::new("test.ca"), false);
+		assert!(!plain.contains('\x1B'), "{:?}", plain);
+		assert!(plain.ends_with("  |\n  = note: a note\n  = help: some help\n"));
+		let colored = diagnostic.render(source, Path::new("test.ca"), true);
+		assert!(colored.starts_with(STYLE_ERROR));
+		assert!(colored.contains(&format!("{}^{}", STYLE_ERROR, STYLE_RESET)));
+	}
+
+	#[test]
+	fn locations() {
+		let diagnostic = Diagnostic::error("E0000", "message").with_note("a note");
+		assert_eq!(
+			diagnostic.render("", Path::new("test.ca"), false),
+			"error[E0000]: message\n --> test.ca\n |\n = note: a note\n"
+		);
+		let location = Location {
+			offset: 2,
+			line: 0,
+			column: 2,
+		};
+		let error = ParseError::NoneMatched {
+			context: InputContext::default().at(location),
+		};
+		let rendered = Diagnostic::from(&error).render("a ]\n", Path::new("test.ca"), false);
+		assert!(rendered.contains(" --> test.ca:1:3\n"), "{}", rendered);
+	}
+
+	#[test]
+	fn indentation() {
+		let error = ParseError::InconsistentIndentation {
+			context: InputContext::default(),
+			expected: format!("tabs"),
+			found: format!("4 spaces"),
+		};
+		let diagnostic = Diagnostic::from(&error);
+		assert_eq!(diagnostic.label, Some(format!("indented with 4 spaces")));
+		assert_eq!(
+			diagnostic.notes,
+			vec![format!("the file is indented with tabs")]
+		);
+		let help = "indent each file with either tabs or the same number of spaces per level";
+		assert_eq!(diagnostic.help, Some(help.to_string()));
+	}
+}
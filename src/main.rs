@@ -143,10 +143,26 @@ use structopt::StructOpt;
 struct Opt {
 	#[structopt(short = "d", long = "debug", help = "Prints additional debug output")]
 	debug: bool,
+	#[structopt(long = "no-color", help = "Disables colored diagnostic output")]
+	no_color: bool,
+	#[structopt(
+		short = "I",
+		long = "include",
+		parse(from_os_str),
+		help = "Additional module search path"
+	)]
+	include: Vec<std::path::PathBuf>,
+	#[structopt(
+		long = "indent",
+		default_value = "detect",
+		help = "Indentation unit, `detect`, `tabs` or the number of spaces"
+	)]
+	indent: parser2::Indentation,
 	#[structopt(parse(from_os_str), help = "Carina source file")]
 	input: std::path::PathBuf,
 }
 
+mod diagnostics;
 mod parser2;
 
 fn main() {
@@ -166,14 +182,30 @@ fn main() {
 
 	let time_start = Utc::now();
 	let success: bool;
-	match parser2::parse(opt.input) {
+	let options = parser2::ParseOptions {
+		indentation: opt.indent,
+	};
+	let mut loader = parser2::ModuleLoader::new(opt.include.clone(), options);
+	match loader.load(&opt.input) {
 		Err(err) => {
 			error!("{:?}", err);
 			success = false;
 		}
-		Ok(info) => {
-			info!("{}", info.message);
-			success = true;
+		Ok(path) => {
+			let color = !opt.no_color;
+			for module in loader.modules() {
+				for error in &module.info.errors {
+					let diagnostic = diagnostics::Diagnostic::from(error);
+					eprint!(
+						"{}",
+						diagnostic.render(&module.info.source, &module.path, color)
+					);
+				}
+			}
+			if let Some(module) = loader.get(&path) {
+				info!("{:#?}", module.info.block);
+			}
+			success = loader.modules().iter().all(|x| x.info.errors.is_empty());
 		}
 	}
 	let time_end = Utc::now();
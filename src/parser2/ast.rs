@@ -0,0 +1,814 @@
+///
+/// Typed syntax tree lowered from parsed terms
+///
+use super::literals::*;
+use super::syntax::{BindingKind, Comments, FundamentalKind};
+use super::types::*;
+
+#[derive(Debug, Clone, Default)]
+pub struct Block {
+	pub expressions: Vec<Expression>,
+	pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Expression {
+	pub kind: ExpressionKind,
+	pub comments: Comments,
+	pub tags: Vec<Tag>,
+	///
+	/// `!` before the expression
+	///
+	pub compile_time: bool,
+	pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExpressionKind {
+	Binding(Binding),
+	Implementation(Implementation),
+	Import(Import),
+	Node(Node),
+	Error(ParseError),
+}
+
+///
+/// `target : annotation = value` or `target <operator> value`,
+/// the value is missing if neither a value nor a block follows the operator
+///
+#[derive(Debug, Clone)]
+pub struct Binding {
+	pub kind: BindingKind,
+	pub target: Pattern,
+	pub annotation: Option<Type>,
+	pub value: Option<Node>,
+	///
+	/// Bound with `:!` or `!` before the value
+	///
+	pub compile_time: bool,
+}
+
+///
+/// `Trait :+ Type` followed by the definitions of the trait members for the type
+///
+#[derive(Debug, Clone)]
+pub struct Implementation {
+	pub trait_type: Type,
+	pub target: Type,
+	pub block: Block,
+}
+
+///
+/// `im module` importing the members of a module into the enclosing block
+///
+#[derive(Debug, Clone)]
+pub struct Import {
+	pub module: Node,
+}
+
+///
+/// `@name arguments` on the lines before an expression or trailing it, aliases are already expanded
+///
+#[derive(Debug, Clone)]
+pub struct Tag {
+	pub name: Identifier,
+	pub arguments: Vec<Node>,
+	pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identifier {
+	pub name: String,
+	pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Node {
+	Identifier(Identifier),
+	///
+	/// `_`, its meaning depends on the enclosing fundamental
+	///
+	Placeholder(Span),
+	Literal {
+		literal: Literal,
+		span: Span,
+	},
+	///
+	/// `object.member`
+	///
+	Member {
+		object: Box<Node>,
+		member: Identifier,
+		span: Span,
+	},
+	///
+	/// Function applied to juxtaposed arguments, or evaluated without arguments at the start of a subexpression
+	///
+	Call {
+		function: Box<Node>,
+		arguments: Vec<Node>,
+		span: Span,
+	},
+	Infix {
+		operator: String,
+		lhs: Box<Node>,
+		rhs: Box<Node>,
+		span: Span,
+	},
+	Prefix {
+		operator: String,
+		operand: Box<Node>,
+		span: Span,
+	},
+	///
+	/// `start..end`, or `start..=end` including the end
+	///
+	Range {
+		start: Box<Node>,
+		end: Box<Node>,
+		inclusive: bool,
+		span: Span,
+	},
+	///
+	/// `..container`, the elements of the container in place of it
+	///
+	Spread {
+		operand: Box<Node>,
+		span: Span,
+	},
+	///
+	/// `(a b)`
+	///
+	Tuple {
+		elements: Vec<Node>,
+		span: Span,
+	},
+	Block(Block),
+	Fundamental(Fundamental),
+	///
+	/// `'Type`, a type used as a value
+	///
+	TypeLiteral {
+		annotation: Box<Type>,
+		span: Span,
+	},
+	///
+	/// `$value`, a copy instead of moving the value
+	///
+	Copy {
+		operand: Box<Node>,
+		span: Span,
+	},
+	///
+	/// `&value`, a reference to the value
+	///
+	Reference {
+		operand: Box<Node>,
+		span: Span,
+	},
+	///
+	/// Symbol without a meaning of its own
+	///
+	Symbol {
+		text: String,
+		span: Span,
+	},
+	Error {
+		error: ParseError,
+		span: Span,
+	},
+}
+
+#[derive(Debug, Clone)]
+pub enum Fundamental {
+	Structure(Structure),
+	Function(Function),
+	Trait(Trait),
+	Enumeration(Enumeration),
+	Module(Module),
+	Match(Match),
+	///
+	/// Fundamental without a dedicated form, with its arguments and block as written
+	///
+	Other {
+		kind: FundamentalKind,
+		arguments: Vec<Node>,
+		block: Option<Block>,
+		span: Span,
+	},
+}
+
+///
+/// `st Parameters... member: Type = default`, members can follow inline, in `{}` or in the block.
+/// Open structures ending with `_` match structures with further members.
+///
+#[derive(Debug, Clone)]
+pub struct Structure {
+	pub parameters: Vec<Identifier>,
+	pub members: Vec<Member>,
+	pub open: bool,
+	pub span: Span,
+}
+
+///
+/// `tr Parameters... {members}` declaring the members of implementing types, `%` refers to the implementing type.
+/// Definitions `name :: value` in the block are provided to all implementing types.
+///
+#[derive(Debug, Clone)]
+pub struct Trait {
+	pub parameters: Vec<TypeParameter>,
+	pub members: Vec<Member>,
+	pub definitions: Vec<Binding>,
+	pub span: Span,
+}
+
+///
+/// `Name` or `Name: Bound`
+///
+#[derive(Debug, Clone)]
+pub struct TypeParameter {
+	pub name: Identifier,
+	pub bound: Option<Type>,
+	pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Member {
+	pub name: Identifier,
+	pub annotation: Type,
+	pub default: Option<Node>,
+	pub comments: Comments,
+	pub tags: Vec<Tag>,
+	pub span: Span,
+}
+
+///
+/// `fn parameters... -> Result => body`, the body can follow as a block instead of `=>`.
+/// Functions without a body are prototypes.
+///
+#[derive(Debug, Clone)]
+pub struct Function {
+	pub parameters: Vec<Parameter>,
+	pub result: Option<Box<Type>>,
+	pub body: Option<Box<Node>>,
+	///
+	/// `!fn`
+	///
+	pub compile_time: bool,
+	pub span: Span,
+}
+
+///
+/// `pattern: Type`, a literal pattern specializes the function for that value and `...` accepts variadic arguments
+///
+#[derive(Debug, Clone)]
+pub struct Parameter {
+	pub pattern: Pattern,
+	pub annotation: Option<Type>,
+	pub passing: Passing,
+	pub variadic: bool,
+	pub span: Span,
+}
+
+///
+/// Values are moved by default, `$` copies and `&` references them
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Passing {
+	Move,
+	Copy,
+	Reference,
+}
+
+///
+/// `en Type` with variants in the block, backed by `U32` unless a type is given
+///
+#[derive(Debug, Clone)]
+pub struct Enumeration {
+	pub backing: Option<Box<Type>>,
+	pub variants: Vec<Variant>,
+	pub span: Span,
+}
+
+///
+/// `Name` or `Name = value`
+///
+#[derive(Debug, Clone)]
+pub struct Variant {
+	pub name: Identifier,
+	pub value: Option<Node>,
+	pub comments: Comments,
+	pub tags: Vec<Tag>,
+	pub span: Span,
+}
+
+///
+/// `md "name" "version"`, the name is resolved to a file by the module loader
+///
+#[derive(Debug, Clone)]
+pub struct Module {
+	pub name: String,
+	pub version: Option<String>,
+	pub span: Span,
+}
+
+///
+/// `mt value` with an arm for each pattern in the block
+///
+#[derive(Debug, Clone)]
+pub struct Match {
+	pub value: Box<Node>,
+	pub arms: Vec<Arm>,
+	pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Arm {
+	pub pattern: ArmPattern,
+	pub body: Block,
+	pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ArmPattern {
+	///
+	/// `_`, matches any value
+	///
+	Wildcard(Span),
+	///
+	/// Literal compared for equality
+	///
+	Value(Node),
+	///
+	/// `eq 100`, the function is called with the matched value followed by the arguments
+	///
+	Guard {
+		function: Node,
+		arguments: Vec<Node>,
+		span: Span,
+	},
+	///
+	/// `S64` or a destructuring `st {a: S32}` binding the members in the arm body
+	///
+	Type(Type),
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+	Identifier(Identifier),
+	Placeholder(Span),
+	Literal {
+		literal: Literal,
+		span: Span,
+	},
+	///
+	/// `(a b)`
+	///
+	Tuple {
+		elements: Vec<Pattern>,
+		span: Span,
+	},
+	///
+	/// `pattern: Type`
+	///
+	Typed {
+		pattern: Box<Pattern>,
+		annotation: Type,
+		span: Span,
+	},
+	///
+	/// Value matched by equality, or a target that is not a plain name like `a.b`
+	///
+	Value(Node),
+	Error {
+		error: ParseError,
+		span: Span,
+	},
+}
+
+#[derive(Debug, Clone)]
+pub enum Type {
+	///
+	/// Type referred to by name with type arguments, e.g. `Option I32` or `math.Vector 3`
+	///
+	Named {
+		path: Vec<Identifier>,
+		arguments: Vec<Type>,
+		span: Span,
+	},
+	Placeholder(Span),
+	///
+	/// `%`, the type implementing the enclosing trait
+	///
+	Implementor(Span),
+	///
+	/// `'Type`, the type itself rather than a value of it
+	///
+	Literal {
+		annotation: Box<Type>,
+		span: Span,
+	},
+	///
+	/// `&Type`, a reference to a value of the type
+	///
+	Reference {
+		annotation: Box<Type>,
+		span: Span,
+	},
+	///
+	/// `A | B`, a value of one of the variants
+	///
+	Sum {
+		variants: Vec<Type>,
+		span: Span,
+	},
+	///
+	/// `A + B`, a value usable as any of the variants
+	///
+	Union {
+		variants: Vec<Type>,
+		span: Span,
+	},
+	Fundamental(Box<Fundamental>),
+	///
+	/// Value used as a type, e.g. for specialization
+	///
+	Value(Node),
+	Error {
+		error: ParseError,
+		span: Span,
+	},
+}
+
+impl Node {
+	pub fn span(&self) -> Span {
+		match self {
+			Node::Identifier(Identifier { span, .. })
+			| Node::Placeholder(span)
+			| Node::Literal { span, .. }
+			| Node::Member { span, .. }
+			| Node::Call { span, .. }
+			| Node::Infix { span, .. }
+			| Node::Prefix { span, .. }
+			| Node::Range { span, .. }
+			| Node::Spread { span, .. }
+			| Node::Tuple { span, .. }
+			| Node::Block(Block { span, .. })
+			| Node::TypeLiteral { span, .. }
+			| Node::Copy { span, .. }
+			| Node::Reference { span, .. }
+			| Node::Symbol { span, .. }
+			| Node::Error { span, .. } => *span,
+			Node::Fundamental(fundamental) => fundamental.span(),
+		}
+	}
+}
+impl Fundamental {
+	pub fn span(&self) -> Span {
+		match self {
+			Fundamental::Structure(Structure { span, .. })
+			| Fundamental::Function(Function { span, .. })
+			| Fundamental::Trait(Trait { span, .. })
+			| Fundamental::Enumeration(Enumeration { span, .. })
+			| Fundamental::Module(Module { span, .. })
+			| Fundamental::Match(Match { span, .. })
+			| Fundamental::Other { span, .. } => *span,
+		}
+	}
+}
+impl Enumeration {
+	///
+	/// Values of the variants counting up from `1` or from the previous integer value,
+	/// `None` following a value that isn't an integer literal
+	///
+	pub fn values(&self) -> Vec<Option<i128>> {
+		let mut next = Some(1);
+		self.variants
+			.iter()
+			.map(|variant| {
+				let value = match &variant.value {
+					None => next,
+					Some(Node::Literal {
+						literal: Literal::Integer(value),
+						..
+					}) => Some(*value),
+					Some(_) => None,
+				};
+				next = value.map(|x| x + 1);
+				value
+			})
+			.collect()
+	}
+}
+impl Match {
+	///
+	/// Arm matching every value that reaches it, later arms are unreachable
+	///
+	pub fn catch_all(&self) -> Option<&Arm> {
+		self.arms.iter().find(|x| match x.pattern {
+			ArmPattern::Wildcard(_) => true,
+			_ => false,
+		})
+	}
+}
+impl ArmPattern {
+	pub fn span(&self) -> Span {
+		match self {
+			ArmPattern::Wildcard(span) | ArmPattern::Guard { span, .. } => *span,
+			ArmPattern::Value(node) => node.span(),
+			ArmPattern::Type(annotation) => annotation.span(),
+		}
+	}
+}
+impl Pattern {
+	pub fn span(&self) -> Span {
+		match self {
+			Pattern::Identifier(Identifier { span, .. })
+			| Pattern::Placeholder(span)
+			| Pattern::Literal { span, .. }
+			| Pattern::Tuple { span, .. }
+			| Pattern::Typed { span, .. }
+			| Pattern::Error { span, .. } => *span,
+			Pattern::Value(node) => node.span(),
+		}
+	}
+}
+impl Type {
+	pub fn span(&self) -> Span {
+		match self {
+			Type::Named { span, .. }
+			| Type::Placeholder(span)
+			| Type::Implementor(span)
+			| Type::Literal { span, .. }
+			| Type::Reference { span, .. }
+			| Type::Sum { span, .. }
+			| Type::Union { span, .. }
+			| Type::Error { span, .. } => *span,
+			Type::Fundamental(fundamental) => fundamental.span(),
+			Type::Value(node) => node.span(),
+		}
+	}
+}
+
+///
+/// Traversal of the expressions and nodes in a block, overridden methods call `walk_*` to visit the children
+///
+pub trait Visitor {
+	fn block(&mut self, block: &Block) {
+		walk_block(self, block)
+	}
+	fn expression(&mut self, expression: &Expression) {
+		walk_expression(self, expression)
+	}
+	fn node(&mut self, node: &Node) {
+		walk_node(self, node)
+	}
+	fn fundamental(&mut self, fundamental: &Fundamental) {
+		walk_fundamental(self, fundamental)
+	}
+	fn member(&mut self, member: &Member) {
+		walk_member(self, member)
+	}
+	fn variant(&mut self, variant: &Variant) {
+		walk_variant(self, variant)
+	}
+	fn parameter(&mut self, parameter: &Parameter) {
+		walk_parameter(self, parameter)
+	}
+	fn arm(&mut self, arm: &Arm) {
+		walk_arm(self, arm)
+	}
+	fn pattern(&mut self, pattern: &Pattern) {
+		walk_pattern(self, pattern)
+	}
+	fn annotation(&mut self, annotation: &Type) {
+		walk_type(self, annotation)
+	}
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &Block) {
+	for expression in &block.expressions {
+		visitor.expression(expression);
+	}
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &Expression) {
+	match &expression.kind {
+		ExpressionKind::Binding(binding) => walk_binding(visitor, binding),
+		ExpressionKind::Implementation(implementation) => {
+			visitor.annotation(&implementation.trait_type);
+			visitor.annotation(&implementation.target);
+			visitor.block(&implementation.block);
+		}
+		ExpressionKind::Import(import) => visitor.node(&import.module),
+		ExpressionKind::Node(node) => visitor.node(node),
+		ExpressionKind::Error(_) => {}
+	}
+}
+
+pub fn walk_node<V: Visitor + ?Sized>(visitor: &mut V, node: &Node) {
+	match node {
+		Node::Member { object, .. } => visitor.node(object),
+		Node::Call {
+			function,
+			arguments,
+			..
+		} => {
+			visitor.node(function);
+			arguments.iter().for_each(|x| visitor.node(x));
+		}
+		Node::Infix { lhs, rhs, .. } => {
+			visitor.node(lhs);
+			visitor.node(rhs);
+		}
+		Node::Prefix { operand, .. }
+		| Node::Copy { operand, .. }
+		| Node::Reference { operand, .. }
+		| Node::Spread { operand, .. } => visitor.node(operand),
+		Node::Range { start, end, .. } => {
+			visitor.node(start);
+			visitor.node(end);
+		}
+		Node::Tuple { elements, .. } => elements.iter().for_each(|x| visitor.node(x)),
+		Node::Block(block) => visitor.block(block),
+		Node::Fundamental(fundamental) => visitor.fundamental(fundamental),
+		Node::TypeLiteral { annotation, .. } => visitor.annotation(annotation),
+		Node::Identifier(_)
+		| Node::Placeholder(_)
+		| Node::Literal { .. }
+		| Node::Symbol { .. }
+		| Node::Error { .. } => {}
+	}
+}
+
+pub fn walk_fundamental<V: Visitor + ?Sized>(visitor: &mut V, fundamental: &Fundamental) {
+	match fundamental {
+		Fundamental::Structure(structure) => {
+			structure.members.iter().for_each(|x| visitor.member(x));
+		}
+		Fundamental::Function(function) => {
+			function
+				.parameters
+				.iter()
+				.for_each(|x| visitor.parameter(x));
+			if let Some(result) = &function.result {
+				visitor.annotation(result);
+			}
+			if let Some(body) = &function.body {
+				visitor.node(body);
+			}
+		}
+		Fundamental::Trait(declaration) => {
+			declaration.members.iter().for_each(|x| visitor.member(x));
+			for definition in &declaration.definitions {
+				walk_binding(visitor, definition);
+			}
+		}
+		Fundamental::Enumeration(enumeration) => {
+			if let Some(backing) = &enumeration.backing {
+				visitor.annotation(backing);
+			}
+			enumeration.variants.iter().for_each(|x| visitor.variant(x));
+		}
+		Fundamental::Match(matching) => {
+			visitor.node(&matching.value);
+			matching.arms.iter().for_each(|x| visitor.arm(x));
+		}
+		Fundamental::Other {
+			arguments, block, ..
+		} => {
+			arguments.iter().for_each(|x| visitor.node(x));
+			if let Some(block) = block {
+				visitor.block(block);
+			}
+		}
+		Fundamental::Module(_) => {}
+	}
+}
+
+pub fn walk_member<V: Visitor + ?Sized>(visitor: &mut V, member: &Member) {
+	visitor.annotation(&member.annotation);
+	if let Some(default) = &member.default {
+		visitor.node(default);
+	}
+}
+
+pub fn walk_variant<V: Visitor + ?Sized>(visitor: &mut V, variant: &Variant) {
+	if let Some(value) = &variant.value {
+		visitor.node(value);
+	}
+}
+
+pub fn walk_binding<V: Visitor + ?Sized>(visitor: &mut V, binding: &Binding) {
+	visitor.pattern(&binding.target);
+	if let Some(annotation) = &binding.annotation {
+		visitor.annotation(annotation);
+	}
+	if let Some(value) = &binding.value {
+		visitor.node(value);
+	}
+}
+
+pub fn walk_parameter<V: Visitor + ?Sized>(visitor: &mut V, parameter: &Parameter) {
+	visitor.pattern(&parameter.pattern);
+	if let Some(annotation) = &parameter.annotation {
+		visitor.annotation(annotation);
+	}
+}
+
+pub fn walk_arm<V: Visitor + ?Sized>(visitor: &mut V, arm: &Arm) {
+	match &arm.pattern {
+		ArmPattern::Wildcard(_) => {}
+		ArmPattern::Value(value) => visitor.node(value),
+		ArmPattern::Guard {
+			function,
+			arguments,
+			..
+		} => {
+			visitor.node(function);
+			arguments.iter().for_each(|x| visitor.node(x));
+		}
+		ArmPattern::Type(annotation) => visitor.annotation(annotation),
+	}
+	visitor.block(&arm.body);
+}
+
+pub fn walk_pattern<V: Visitor + ?Sized>(visitor: &mut V, pattern: &Pattern) {
+	match pattern {
+		Pattern::Tuple { elements, .. } => elements.iter().for_each(|x| visitor.pattern(x)),
+		Pattern::Typed {
+			pattern,
+			annotation,
+			..
+		} => {
+			visitor.pattern(pattern);
+			visitor.annotation(annotation);
+		}
+		Pattern::Value(value) => visitor.node(value),
+		Pattern::Identifier(_)
+		| Pattern::Placeholder(_)
+		| Pattern::Literal { .. }
+		| Pattern::Error { .. } => {}
+	}
+}
+
+pub fn walk_type<V: Visitor + ?Sized>(visitor: &mut V, annotation: &Type) {
+	match annotation {
+		Type::Named { arguments, .. } => arguments.iter().for_each(|x| visitor.annotation(x)),
+		Type::Literal { annotation, .. } | Type::Reference { annotation, .. } => {
+			visitor.annotation(annotation)
+		}
+		Type::Sum { variants, .. } | Type::Union { variants, .. } => {
+			variants.iter().for_each(|x| visitor.annotation(x))
+		}
+		Type::Fundamental(fundamental) => visitor.fundamental(fundamental),
+		Type::Value(value) => visitor.node(value),
+		Type::Placeholder(_) | Type::Implementor(_) | Type::Error { .. } => {}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::{parse_source, ParseOptions};
+	use super::*;
+
+	///
+	/// Names in visiting order, from identifiers, named patterns and named types
+	///
+	#[derive(Default)]
+	struct Names(Vec<String>);
+	impl Visitor for Names {
+		fn node(&mut self, node: &Node) {
+			if let Node::Identifier(identifier) = node {
+				self.0.push(identifier.name.clone());
+			}
+			walk_node(self, node)
+		}
+		fn pattern(&mut self, pattern: &Pattern) {
+			if let Pattern::Identifier(identifier) = pattern {
+				self.0.push(identifier.name.clone());
+			}
+			walk_pattern(self, pattern)
+		}
+		fn annotation(&mut self, annotation: &Type) {
+			if let Type::Named { path, .. } = annotation {
+				self.0.extend(path.iter().map(|x| x.name.clone()));
+			}
+			walk_type(self, annotation)
+		}
+	}
+
+	#[test]
+	fn walk() {
+		let source =
+			"f :: fn a: Option T -> U => a\nmt x\n\teq limit\n\t\t1\n\tst {b: Inner}\n\t\t2\n";
+		let info = parse_source(source.to_string(), &ParseOptions::default());
+		assert!(info.errors.is_empty(), "{:?}", info.errors);
+		let mut names = Names::default();
+		names.block(&info.block);
+		let expected = [
+			"f", "a", "Option", "T", "U", "a", "x", "eq", "limit", "Inner",
+		];
+		assert_eq!(names.0, expected);
+	}
+}
@@ -2,9 +2,13 @@ use itertools::*;
 use std::fmt::{Debug, Display};
 use std::iter::{Iterator, Peekable};
 use trace::trace;
+use unicode_segmentation::UnicodeSegmentation;
 
 trace::init_depth_var!();
 
+use super::lexer::*;
+use super::literals::*;
+use super::syntax::*;
 use super::types::Output::*;
 use super::types::ParseError::*;
 use super::types::*;
@@ -20,9 +24,36 @@ pub fn eof<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<()
 		_ => Ok(()),
 	}
 }
-// pub fn literal<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<T> {
-// 	// TODO
-// }
+pub fn literal<'l, T: InputIterItem, I: InputIter<T>>(
+	literal: &'l str,
+) -> impl ParseFn<T, I, String> + 'l {
+	move |input: InputRef<T, I>| -> Output<String> {
+		let mut lookahead = input.clone();
+		let mut found = String::new();
+		for expected in literal.graphemes(true) {
+			match lookahead.next() {
+				Ok(x) => {
+					found.push_str(x.as_ref());
+					if x.as_ref() != expected {
+						return Error(UnexpectedCharacter {
+							context: input.context,
+							expected: format!("`{}`", literal),
+							found,
+						});
+					}
+				}
+				_ => {
+					return Error(UnexpectedEOF {
+						context: input.context,
+						expected: format!("`{}`", literal),
+					})
+				}
+			}
+		}
+		(*input) = lookahead;
+		Ok(found)
+	}
+}
 pub fn tab<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<T> {
 	let x = input.clone().next()?;
 	if is_tab(&x) {
@@ -76,17 +107,56 @@ pub fn any_regular_char<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>)
 		found: format!("{}", x),
 	})
 }
-pub fn any_special_char<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<T> {
-	let x = input.clone().next()?;
-	if is_special(&x) {
-		input.next()?;
-		return Ok(x);
+
+pub fn satisfy<T: InputIterItem, I: InputIter<T>>(
+	predicate: impl Fn(&T) -> bool,
+	expected: &'static str,
+) -> impl ParseFn<T, I, T> {
+	move |input: InputRef<T, I>| -> Output<T> {
+		let x = input.clone().next()?;
+		if predicate(&x) {
+			input.next()?;
+			return Ok(x);
+		}
+		Error(UnexpectedCharacter {
+			context: input.context,
+			expected: format!("{}", expected),
+			found: format!("{}", x),
+		})
+	}
+}
+
+pub fn token<I: InputIter<Token>>(kind: TokenKind) -> impl ParseFn<Token, I, Token> {
+	move |input: InputRef<Token, I>| -> Output<Token> {
+		let x = input.clone().next()?;
+		if x.kind == kind {
+			input.next()?;
+			return Ok(x);
+		}
+		Error(UnexpectedToken {
+			context: input.context.at(x.span.start),
+			expected: format!("{}", kind),
+			found: format!("{}", x),
+		})
+	}
+}
+pub fn symbol<'s, I: InputIter<Token>>(text: &'s str) -> impl ParseFn<Token, I, Token> + 's {
+	move |input: InputRef<Token, I>| -> Output<Token> {
+		let x = input.clone().next()?;
+		if x.text == text
+			&& (x.kind == TokenKind::Operator
+				|| x.kind == TokenKind::Delimiter
+				|| x.kind == TokenKind::Identifier)
+		{
+			input.next()?;
+			return Ok(x);
+		}
+		Error(UnexpectedToken {
+			context: input.context.at(x.span.start),
+			expected: format!("`{}`", text),
+			found: format!("{}", x),
+		})
 	}
-	Error(UnexpectedCharacter {
-		context: input.context,
-		expected: format!("any special character"),
-		found: format!("{}", x),
-	})
 }
 
 pub fn wrap<T: InputIterItem, I: InputIter<T>, R>(
@@ -95,6 +165,12 @@ pub fn wrap<T: InputIterItem, I: InputIter<T>, R>(
 	move |input: InputRef<T, I>| -> Output<R> { parser(input) }
 }
 
+pub fn peek<T: InputIterItem, I: InputIter<T>, R>(
+	parser: impl ParseFn<T, I, R>,
+) -> impl ParseFn<T, I, R> {
+	move |input: InputRef<T, I>| -> Output<R> { parser(&mut input.clone()) }
+}
+
 pub fn discard<T: InputIterItem, I: InputIter<T>, R>(
 	parser: impl ParseFn<T, I, R>,
 ) -> impl ParseFn<T, I, ()> {
@@ -131,35 +207,9 @@ pub fn any_of<'of, T: InputIterItem, I: InputIter<T>, R>(
 			}
 		}
 		(*input) = input_orig;
-		Error(NoneMatched)
-	}
-}
-
-pub fn either<T: InputIterItem, I: InputIter<T>, R: From<L>, L: From<R>>(
-	l: impl ParseFn<T, I, L>,
-	r: impl ParseFn<T, I, R>,
-) -> impl ParseFn<T, I, R> {
-	move |input: InputRef<T, I>| {
-		let input_orig = input.clone();
-		let resultl = l(input);
-		if let Ok(_) = resultl {
-			return resultl.map_value();
-		};
-		(*input) = input_orig.clone();
-		let resultr = r(input);
-		match resultr {
-			Ok(_) => resultr.map_value(),
-			Partial { .. } => {
-				if let Partial { .. } = resultl {
-					return resultl.map_value();
-				}
-				resultr.map_value()
-			}
-			_ => {
-				(*input) = input_orig;
-				resultr.map_value()
-			}
-		}
+		Error(NoneMatched {
+			context: input.context,
+		})
 	}
 }
 
@@ -214,11 +264,12 @@ pub fn one_or_more<T: InputIterItem, I: InputIter<T>, R>(
 ) -> impl ParseFn<T, I, Vec<R>> {
 	move |input: InputRef<T, I>| {
 		let mut result = Vec::new();
+		let context = input.context;
 		let next = parser(input);
 		if let Ok(next) = next {
 			result.push(next);
 		} else {
-			return Error(NoneMatched);
+			return Error(NoneMatched { context });
 		}
 		let mut more = zero_or_more(&parser)(input)?;
 		result.append(&mut more);
@@ -260,69 +311,753 @@ pub fn multiple<T: InputIterItem, I: InputIter<T>, R>(
 	}
 }
 
-pub fn wrap_ok<T: InputIterItem, I: InputIter<T>, R>(
-	parser: impl ParseFn<T, I, R>,
-) -> impl ParseFn<T, I, Output<R>> {
-	move |input: InputRef<T, I>| -> Output<Output<R>> { Ok(parser(input)) }
+pub fn identifier(input: InputRef<Token, impl InputIter<Token>>) -> Output<Term> {
+	let x = token(TokenKind::Identifier)(input)?;
+	Ok(Term::Identifier {
+		name: x.text,
+		span: x.span,
+	})
+}
+
+pub fn term(input: InputRef<Token, impl InputIter<Token>>) -> Output<Term> {
+	let x = input.clone().next()?;
+	match x.kind {
+		TokenKind::Identifier => identifier(input),
+		TokenKind::Integer | TokenKind::Float | TokenKind::String => literal_term(input),
+		TokenKind::Delimiter if x.text == "[" => subexpression(input),
+		TokenKind::Delimiter if x.text == "(" => group(input),
+		TokenKind::Delimiter if x.text == "{" => brace_block(input),
+		TokenKind::Indent
+		| TokenKind::Dedent
+		| TokenKind::Newline
+		| TokenKind::Comment
+		| TokenKind::Delimiter => Error(UnexpectedToken {
+			context: input.context.at(x.span.start),
+			expected: format!("expression"),
+			found: format!("{}", x),
+		}),
+		_ => {
+			input.next()?;
+			Ok(Term::Symbol {
+				text: x.text,
+				span: x.span,
+			})
+		}
+	}
 }
 
-pub fn all_chars<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<Vec<T>> {
-	all_until(any_of(&[&space, &any_regular_char, &newline]), eof)(input)
+///
+/// Parse a term, joining it with a following `..` or `..=` and term into a range that has to be written without spaces,
+/// or a term directly preceded by `..` into a spread
+///
+pub fn range(input: InputRef<Token, impl InputIter<Token>>) -> Output<Term> {
+	if joined(input, &[".."]) {
+		let spread = input.next()?.span;
+		let (operand, error) = term(input).recoverable()?;
+		let span = spread.to(operand.span());
+		return Output::partial(
+			Term::Spread {
+				operand: Box::new(operand),
+				span,
+			},
+			error,
+		);
+	}
+	let (start, error) = term(input).recoverable()?;
+	let (inclusive, spaced) = match range_operator(input) {
+		Some(range) => range,
+		None => return Output::partial(start, error),
+	};
+	let operator = input.next()?.span;
+	if inclusive {
+		input.next()?;
+	}
+	let (end, end_error) = term(input).recoverable()?;
+	let span = start.span().to(end.span());
+	if spaced {
+		let symbol = if inclusive { "..=" } else { ".." };
+		let spaced_error = UnexpectedToken {
+			context: input.context.at(operator.start),
+			expected: format!("the range written without spaces around `{}`", symbol),
+			found: format!("{}", symbol),
+		};
+		return Partial {
+			result: Term::Error {
+				error: spaced_error.clone(),
+				span,
+			},
+			error: error.or(end_error).unwrap_or(spaced_error),
+		};
+	}
+	Output::partial(
+		Term::Range {
+			start: Box::new(start),
+			end: Box::new(end),
+			inclusive,
+			span,
+		},
+		error.or(end_error),
+	)
 }
 
-#[derive(Debug, Clone)]
-pub enum Block {
-	Block(Vec<Expression>),
-	None,
+///
+/// Whether the next tokens are `..` or `..=` followed by a term, returning if the range is inclusive
+/// and if it is written with spaces. A `..` with a space before but not after starts a spread instead.
+///
+fn range_operator(input: InputRef<Token, impl InputIter<Token>>) -> Option<(bool, bool)> {
+	let lookahead = &mut input.clone();
+	let operator = match lookahead.next() {
+		Ok(x) if x.kind == TokenKind::Operator && x.text == ".." => x,
+		_ => return None,
+	};
+	let mut next = match lookahead.next() {
+		Ok(x) => x,
+		_ => return None,
+	};
+	let inclusive = next.kind == TokenKind::Operator && next.text == "=" && next.trivia.is_empty();
+	if inclusive {
+		next = match lookahead.next() {
+			Ok(x) => x,
+			_ => return None,
+		};
+	}
+	let spread = !operator.trivia.is_empty() && !inclusive && next.trivia.is_empty();
+	match starts_term(&next) && !spread {
+		true => Some((
+			inclusive,
+			!operator.trivia.is_empty() || !next.trivia.is_empty(),
+		)),
+		false => None,
+	}
 }
 
-#[derive(Debug, Clone)]
-pub struct Expression {
-	identifiers: Vec<String>,
-	block: Block,
+///
+/// Whether the next tokens are the given operators followed by a token, each written without spaces before it
+///
+fn joined(input: InputRef<Token, impl InputIter<Token>>, operators: &[&str]) -> bool {
+	let lookahead = &mut input.clone();
+	for (index, operator) in operators.iter().enumerate() {
+		match lookahead.next() {
+			Ok(x) if x.kind == TokenKind::Operator && x.text == *operator => {
+				if index > 0 && !x.trivia.is_empty() {
+					return false;
+				}
+			}
+			_ => return false,
+		}
+	}
+	match lookahead.next() {
+		Ok(x) => x.trivia.is_empty() && starts_term(&x),
+		_ => false,
+	}
 }
 
-pub fn dedent<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<()> {
-	let input_orig = input.clone();
-	let result = multiple(input.context.indent, tab)(input);
-	(*input) = input_orig;
-	match result {
-		Ok(_) => Error(NoneMatched),
-		_ => Ok(()),
+fn starts_term(token: &Token) -> bool {
+	match token.kind {
+		TokenKind::Indent | TokenKind::Dedent | TokenKind::Newline | TokenKind::Comment => false,
+		TokenKind::Delimiter => ["[", "(", "{"].contains(&token.text.as_str()),
+		_ => true,
 	}
 }
 
-#[trace]
-pub fn expression<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<Expression> {
-	multiple(input.context.indent, tab)(input);
-	let result = all_until(
-		all_until(any_regular_char, one_or_more(any_of(&[&space]))),
-		any_of(&[&discard(newline), &eof]),
-	)(input);
-	let identifiers = match result {
-		Ok(i) => i,
-		Partial { result, .. } => result,
-		_ => return Error(NoneMatched),
+///
+/// Parse terms up to but excluding the first token matching `end`
+///
+pub fn terms_until(
+	input: InputRef<Token, impl InputIter<Token>>,
+	end: impl Fn(&Token) -> bool,
+) -> Output<Vec<Term>> {
+	let mut terms = Vec::new();
+	let mut first_error = None;
+	while !end(&input.clone().next()?) {
+		let (term, error) = range(input).recoverable()?;
+		terms.push(term);
+		first_error = first_error.or(error);
+	}
+	Output::partial(terms, first_error)
+}
+
+///
+/// Parse terms enclosed in `open` and `close` on a single line
+///
+fn delimited(
+	input: InputRef<Token, impl InputIter<Token>>,
+	open: &'static str,
+	close: &'static str,
+) -> Output<(Vec<Term>, Span)> {
+	let start = symbol(open)(input)?.span;
+	let (terms, mut error) = terms_until(input, |x| {
+		x.kind == TokenKind::Newline || x.kind == TokenKind::Delimiter && x.text == close
+	})
+	.recoverable()?;
+	let terms = pipeline(terms, input.context, &mut error);
+	let end = symbol(close)(input)?.span;
+	Output::partial((terms, start.to(end)), error)
+}
+
+pub fn subexpression(input: InputRef<Token, impl InputIter<Token>>) -> Output<Term> {
+	delimited(input, "[", "]").map(|(terms, span)| Term::Subexpression { terms, span })
+}
+
+pub fn group(input: InputRef<Token, impl InputIter<Token>>) -> Output<Term> {
+	delimited(input, "(", ")").map(|(terms, span)| Term::Group { terms, span })
+}
+
+///
+/// Parse an inline block `{...}` of expressions separated by `,`
+///
+pub fn brace_block(input: InputRef<Token, impl InputIter<Token>>) -> Output<Term> {
+	let start = symbol("{")(input)?.span;
+	let mut exprs = Vec::new();
+	let mut first_error = None;
+	while !peek(symbol("}"))(input).is_ok() {
+		let expr_start = input.clone().next()?.span.start;
+		let (terms, error) = terms_until(input, |x| {
+			x.kind == TokenKind::Newline
+				|| x.kind == TokenKind::Comment
+				|| x.kind == TokenKind::Delimiter && x.text == "}"
+				|| x.kind == TokenKind::Operator && x.text == ","
+		})
+		.recoverable()?;
+		let span = Span::new(expr_start, input.context.location());
+		let (expr, statement_error) =
+			statement(terms, Comments::default(), Block::None, span, input.context)
+				.recoverable()?;
+		exprs.push(expr);
+		first_error = first_error.or(error).or(statement_error);
+		if !symbol(",")(input).is_ok() {
+			break;
+		}
+	}
+	let span = start.to(symbol("}")(input)?.span);
+	let block = Block::Block {
+		expressions: exprs,
+		span,
 	};
-	one_or_more(newline)(input);
-	input.context.indent += 1;
-	let exprsblock = block(input);
-	input.context.indent -= 1;
-	Ok(Expression {
-		identifiers: identifiers.iter().map(|i| i.iter().join("")).collect::<_>(),
-		block: exprsblock,
+	Output::partial(Term::Block { block, span }, first_error)
+}
+
+///
+/// Skip the rest of the current line and any block nested below it,
+/// returning the end of the skipped line
+///
+pub fn recover(input: InputRef<Token, impl InputIter<Token>>) -> Location {
+	let mut depth = 0;
+	let mut end = None;
+	while let Ok(x) = input.clone().next() {
+		match x.kind {
+			TokenKind::Dedent if depth == 0 => break,
+			TokenKind::Dedent => depth -= 1,
+			TokenKind::Indent => depth += 1,
+			TokenKind::Newline => {
+				end.get_or_insert(x.span.start);
+			}
+			_ => {}
+		}
+		input.next();
+		if x.kind != TokenKind::Newline && x.kind != TokenKind::Dedent || depth > 0 {
+			continue;
+		}
+		if let Error(_) = token(TokenKind::Indent)(&mut input.clone()) {
+			break;
+		}
+	}
+	end.unwrap_or_else(|| input.context.location())
+}
+
+///
+/// Parse a line with the block indented below it. A block followed by a less indented block
+/// continues the line like a `{}` block, e.g. the members of a `st` pattern before a match arm body.
+///
+#[trace]
+pub fn expression(input: InputRef<Token, impl InputIter<Token>>) -> Output<Expression> {
+	let start = input.clone().next()?.span.start;
+	let (mut terms, first_error) = terms_until(input, |x| {
+		x.kind == TokenKind::Newline || x.kind == TokenKind::Comment
 	})
+	.recoverable()?;
+	let trailing = match token(TokenKind::Comment)(input) {
+		Ok(x) => Some(Comment::from(x)),
+		_ => None,
+	};
+	token(TokenKind::Newline)(input)?;
+	let mut exprsblock = Block::None;
+	let mut block_error = None;
+	while let Ok(_) = token(TokenKind::Indent)(input) {
+		if let Block::Block { span, .. } = &exprsblock {
+			let span = *span;
+			let block = std::mem::replace(&mut exprsblock, Block::None);
+			terms.push(Term::Block { block, span });
+		}
+		let (inner, error) = block(input).recoverable()?;
+		token(TokenKind::Dedent)(input);
+		exprsblock = inner;
+		block_error = block_error.or(error);
+	}
+	let block = exprsblock;
+	let end = terms.last().map(|x| x.span().end).unwrap_or(start);
+	let span = Span::new(start, end);
+	let comments = Comments {
+		trailing,
+		..Comments::default()
+	};
+	let (expression, statement_error) =
+		statement(terms, comments, block, span, input.context).recoverable()?;
+	Output::partial(expression, first_error.or(statement_error).or(block_error))
+}
+
+///
+/// Split the terms of a statement at its first binding operator before any fundamental, recording error terms for missing sides.
+/// The right-hand side may only be empty if the statement is followed by a block.
+///
+pub fn statement(
+	terms: Vec<Term>,
+	comments: Comments,
+	block: Block,
+	span: Span,
+	context: InputContext,
+) -> Output<Expression> {
+	let found = terms
+		.iter()
+		.enumerate()
+		.take_while(|(_, x)| match x {
+			Term::Identifier { name, .. } => FundamentalKind::from_keyword(name).is_none(),
+			_ => true,
+		})
+		.find_map(|(index, x)| match x {
+			Term::Symbol { text, span } => {
+				BindingKind::from_operator(text).map(|kind| (index, kind, *span))
+			}
+			_ => None,
+		});
+	let (index, kind, operator) = match found {
+		Some(found) => found,
+		None => {
+			let mut first_error = None;
+			let terms = pipeline(terms, context, &mut first_error);
+			let expression = Expression::Expression {
+				terms,
+				comments,
+				block,
+				span,
+			};
+			return Output::partial(expression, first_error);
+		}
+	};
+	let mut lhs = terms;
+	let mut rhs = lhs.split_off(index + 1);
+	lhs.pop();
+	let mut annotation = None;
+	let mut assignment = operator;
+	if kind == BindingKind::Declare {
+		let equals = rhs.iter().position(|x| match x {
+			Term::Symbol { text, .. } => text == "=",
+			_ => false,
+		});
+		if let Some(equals) = equals {
+			let value = rhs.split_off(equals + 1);
+			assignment = rhs.pop().map(|x| x.span()).unwrap_or(operator);
+			annotation = Some(std::mem::replace(&mut rhs, value));
+		}
+	}
+
+	let mut first_error = None;
+	require(
+		&mut lhs,
+		context.at(operator.start),
+		format!("expression before `{}`", kind.operator()),
+		kind.operator().to_string(),
+		&mut first_error,
+	);
+	if let Some(annotation) = &mut annotation {
+		require(
+			annotation,
+			context.at(assignment.start),
+			format!("type before `=`"),
+			format!("="),
+			&mut first_error,
+		);
+	}
+	if let Block::None = block {
+		let operator = match annotation {
+			Some(_) => "=",
+			None => kind.operator(),
+		};
+		require(
+			&mut rhs,
+			context.at(assignment.end),
+			format!("expression after `{}`", operator),
+			format!("{}", TokenKind::Newline),
+			&mut first_error,
+		);
+	}
+	let lhs = pipeline(lhs, context, &mut first_error);
+	let annotation = annotation.map(|terms| pipeline(terms, context, &mut first_error));
+	let rhs = pipeline(rhs, context, &mut first_error);
+	let binding = Binding {
+		kind,
+		lhs,
+		annotation,
+		rhs,
+		operator,
+	};
+	Output::partial(
+		Expression::Binding {
+			binding,
+			comments,
+			block,
+			span,
+		},
+		first_error,
+	)
+}
+
+///
+/// Record an error term if a required operand of an operator is missing
+///
+pub fn require(
+	terms: &mut Vec<Term>,
+	context: InputContext,
+	expected: String,
+	found: String,
+	first_error: &mut Option<ParseError>,
+) {
+	if terms.is_empty() {
+		let location = context.location();
+		let error = UnexpectedToken {
+			context,
+			expected,
+			found,
+		};
+		first_error.get_or_insert(error.clone());
+		terms.push(Term::Error {
+			error,
+			span: Span::new(location, location),
+		});
+	}
+}
+
+///
+/// Fold `|>` and `<|` between terms into left-associative pipes
+///
+pub fn pipeline(
+	terms: Vec<Term>,
+	context: InputContext,
+	first_error: &mut Option<ParseError>,
+) -> Vec<Term> {
+	let mut segments = vec![Vec::new()];
+	let mut operators = Vec::new();
+	for term in terms {
+		let direction = match &term {
+			Term::Symbol { text, .. } => PipeDirection::from_operator(text),
+			_ => None,
+		};
+		match direction {
+			Some(direction) => {
+				operators.push((direction, term.span()));
+				segments.push(Vec::new());
+			}
+			None => {
+				if let Some(segment) = segments.last_mut() {
+					segment.push(term);
+				}
+			}
+		}
+	}
+	let mut segments = segments.into_iter();
+	let mut result = segments.next().unwrap_or_default();
+	for (index, ((direction, operator), mut rhs)) in operators.iter().zip(segments).enumerate() {
+		require(
+			&mut result,
+			context.at(operator.start),
+			format!("expression before `{}`", direction.operator()),
+			direction.operator().to_string(),
+			first_error,
+		);
+		let found = match operators.get(index + 1) {
+			Some((next, _)) => next.operator().to_string(),
+			None => format!("{}", TokenKind::Newline),
+		};
+		require(
+			&mut rhs,
+			context.at(operator.end),
+			format!("expression after `{}`", direction.operator()),
+			found,
+			first_error,
+		);
+		let span = terms_span(&result)
+			.unwrap_or(*operator)
+			.to(terms_span(&rhs).unwrap_or(*operator));
+		result = vec![Term::Pipe {
+			direction: *direction,
+			lhs: result,
+			rhs,
+			operator: *operator,
+			span,
+		}];
+	}
+	result
 }
 
 #[trace]
-pub fn block<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Block {
-	match multiple(input.context.indent, tab)(&mut input.clone()) {
-		Ok(_) => {}
-		_ => return Block::None,
-	}
-	let exprs = all_until(expression, any_of(&[&eof, &dedent]))(input);
-	match exprs {
-		Ok(exprs) if exprs.len() > 0 => Block::Block(exprs),
-		_ => Block::None,
+pub fn block(input: InputRef<Token, impl InputIter<Token>>) -> Output<Block> {
+	let mut exprs = Vec::new();
+	let mut first_error = None;
+	let mut leading = Vec::new();
+	loop {
+		while let Ok(x) = any_of(&[&token(TokenKind::Newline), &token(TokenKind::Comment)])(input) {
+			if x.kind == TokenKind::Comment {
+				leading.push(Comment::from(x));
+			}
+		}
+		if let Ok(_) = any_of(&[&discard(peek(token(TokenKind::Dedent))), &eof])(input) {
+			break;
+		}
+		let input_orig = input.clone();
+		match expression(input) {
+			Ok(mut expr) => {
+				expr.attach_leading(&mut leading);
+				exprs.push(expr);
+			}
+			Partial { mut result, error } => {
+				result.attach_leading(&mut leading);
+				exprs.push(result);
+				first_error.get_or_insert(error);
+			}
+			Error(error) | Critical(error) => {
+				(*input) = input_orig;
+				let start = match input.clone().next() {
+					Ok(x) => x.span.start,
+					_ => input.context.location(),
+				};
+				let end = recover(input);
+				exprs.push(Expression::Error {
+					error: error.clone(),
+					span: Span::new(start, end),
+				});
+				first_error.get_or_insert(error);
+			}
+		}
+	}
+	if exprs.is_empty() {
+		return Ok(Block::None);
+	}
+	let last = exprs.len() - 1;
+	if let Some(comments) = exprs[last].comments_mut() {
+		comments.dangling.append(&mut leading);
+	}
+	let span = exprs[0].full_span().to(exprs[exprs.len() - 1].full_span());
+	let block = Block::Block {
+		expressions: exprs,
+		span,
+	};
+	match first_error {
+		Some(error) => Partial {
+			result: block,
+			error,
+		},
+		None => Ok(block),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse_block(source: &str) -> Block {
+		let graphemes = source.graphemes(true).collect::<Vec<_>>();
+		let (tokens, _) = tokenize(&mut Input::new(graphemes.into_iter()), Indentation::Detect);
+		match block(&mut Input::new(tokens.into_iter())) {
+			Ok(block) | Partial { result: block, .. } => block,
+			output => panic!("parsed to {:?}", output),
+		}
+	}
+
+	///
+	/// Terms of the expression with the sides of a binding around its operator, followed by its block.
+	/// Literals are written `_`.
+	///
+	fn show(expression: &Expression) -> String {
+		let (mut text, block) = match expression {
+			Expression::Expression { terms, block, .. } => (show_terms(terms), block),
+			Expression::Binding { binding, block, .. } => {
+				let mut text = format!("{} {}", show_terms(&binding.lhs), binding.kind.operator());
+				if let Some(annotation) = &binding.annotation {
+					text += &format!(" {} =", show_terms(annotation));
+				}
+				(format!("{} {}", text, show_terms(&binding.rhs)), block)
+			}
+			Expression::Error { .. } => return format!("<error>"),
+		};
+		if let Block::Block { expressions, .. } = block {
+			text += &format!(" {{{}}}", expressions.iter().map(show).join(", "));
+		}
+		text.trim().to_string()
+	}
+
+	fn show_terms(terms: &[Term]) -> String {
+		terms.iter().map(show_term).join(" ")
+	}
+
+	fn show_term(term: &Term) -> String {
+		match term {
+			Term::Identifier { name, .. } => name.clone(),
+			Term::Symbol { text, .. } => text.clone(),
+			Term::Subexpression { terms, .. } => format!("[{}]", show_terms(terms)),
+			Term::Group { terms, .. } => format!("({})", show_terms(terms)),
+			Term::Block { block, .. } => format!("{{{}}}", shapes(block).join(", ")),
+			Term::Error { .. } => format!("<error>"),
+			_ => format!("_"),
+		}
+	}
+
+	fn shapes(block: &Block) -> Vec<String> {
+		match block {
+			Block::Block { expressions, .. } => expressions.iter().map(show).collect(),
+			Block::None => Vec::new(),
+		}
+	}
+
+	fn error_lines(block: &Block) -> Vec<usize> {
+		let errors = block.errors();
+		errors
+			.iter()
+			.filter_map(|x| x.location())
+			.map(|x| x.line)
+			.collect()
+	}
+
+	#[test]
+	fn recovery() {
+		let block = parse_block("a := 1\n) x\nb := 2\n\tc\n:= 3\nd := 4\n");
+		assert_eq!(
+			shapes(&block),
+			vec!["a := _", "<error>", "b := _ {c}", "<error> := _", "d := _"]
+		);
+		assert_eq!(error_lines(&block), vec![1, 4]);
+	}
+
+	#[test]
+	fn nested_recovery() {
+		let block = parse_block("a\n\tb\n\t] y\n\tc\n\t\t) z\n\t\td\ne\n");
+		assert_eq!(shapes(&block), vec!["a {b, <error>, c {<error>, d}}", "e"]);
+		assert_eq!(error_lines(&block), vec![2, 4]);
+	}
+
+	///
+	/// Leading, trailing and dangling comment texts of the expressions in a block
+	///
+	fn comments(block: Block) -> Vec<(Vec<String>, Option<String>, Vec<String>)> {
+		let texts =
+			|comments: &[Comment]| comments.iter().map(|x| x.text.trim().to_string()).collect();
+		let expressions = match block {
+			Block::Block { expressions, .. } => expressions,
+			Block::None => Vec::new(),
+		};
+		let mut result = Vec::new();
+		for mut expression in expressions {
+			if let Some(comments) = expression.comments_mut() {
+				result.push((
+					texts(&comments.leading),
+					comments
+						.trailing
+						.as_ref()
+						.map(|x| x.text.trim().to_string()),
+					texts(&comments.dangling),
+				));
+			}
+		}
+		result
+	}
+
+	#[test]
+	fn attached_comments() {
+		let source =
+			"# first\n# second\na := 1 # trailing\n\nb\n\t# inner\n\tc # inner trailing\n# end\n";
+		let block = parse_block(source);
+		let inner = match &block {
+			Block::Block { expressions, .. } => match &expressions[1] {
+				Expression::Expression { block, .. } => block.clone(),
+				expression => panic!("parsed to {:?}", expression),
+			},
+			Block::None => panic!("parsed to an empty block"),
+		};
+		let strings = |x: &[&str]| x.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+		assert_eq!(
+			comments(block),
+			vec![
+				(
+					strings(&["first", "second"]),
+					Some(format!("trailing")),
+					vec![]
+				),
+				(vec![], None, strings(&["end"])),
+			]
+		);
+		assert_eq!(
+			comments(inner),
+			vec![(strings(&["inner"]), Some(format!("inner trailing")), vec![])]
+		);
+	}
+
+	#[test]
+	fn groupings() {
+		let block = parse_block("s :: st {x: i32}\nfilter [fn e => e] 5..9\na [b (c d)] {e, f := g}\n\th\n{a {b [c]}, d}\n");
+		assert_eq!(
+			shapes(&block),
+			vec![
+				"s :: st {x : i32}",
+				"filter [fn e => e] _",
+				"a [b (c d)] {e, f := g} {h}",
+				"{a {b [c]}, d}",
+			]
+		);
+		assert!(block.errors().is_empty());
+	}
+
+	#[test]
+	fn bindings() {
+		let source = "a :: 1\nb : S64 = 2\nc := d e\nf :! g\nT :+ U\nh :- i\n(j k) =: (k j)\nl <- m\nn : S64\n";
+		let block = parse_block(source);
+		assert_eq!(
+			shapes(&block),
+			vec![
+				"a :: _",
+				"b : S64 = _",
+				"c := d e",
+				"f :! g",
+				"T :+ U",
+				"h :- i",
+				"(j k) =: (k j)",
+				"l <- m",
+				"n : S64",
+			]
+		);
+		let kinds = match &block {
+			Block::Block { expressions, .. } => expressions
+				.iter()
+				.map(|x| match x {
+					Expression::Binding { binding, .. } => binding.kind,
+					expression => panic!("parsed to {:?}", expression),
+				})
+				.collect::<Vec<_>>(),
+			Block::None => Vec::new(),
+		};
+		use BindingKind::*;
+		let expected = vec![
+			Define,
+			Declare,
+			Assign,
+			AssignCompileTime,
+			Add,
+			Remove,
+			Replace,
+			Store,
+			Declare,
+		];
+		assert_eq!(kinds, expected);
+
+		let block = parse_block(":: 1\nb : = 2\nc :=\n");
+		assert_eq!(error_lines(&block), vec![0, 1, 2]);
 	}
 }
@@ -0,0 +1,243 @@
+use super::combinators::*;
+use super::syntax::*;
+use super::types::*;
+
+///
+/// Rewrite pipes into nested call subexpressions:
+/// `a |> f x` becomes `f x [a]` and `f <| a` becomes `f [a]`.
+/// If the succeeding side contains the placeholder `_`, the preceding side is put in its place instead,
+/// so `a <| _.x` becomes `[a].x`.
+///
+pub fn desugar(block: Block) -> Block {
+	match block {
+		Block::Block { expressions, span } => Block::Block {
+			expressions: expressions.into_iter().map(desugar_expression).collect(),
+			span,
+		},
+		Block::None => Block::None,
+	}
+}
+
+fn desugar_expression(expression: Expression) -> Expression {
+	match expression {
+		Expression::Expression {
+			terms,
+			comments,
+			block,
+			span,
+		} => Expression::Expression {
+			terms: desugar_terms(terms),
+			comments,
+			block: desugar(block),
+			span,
+		},
+		Expression::Binding {
+			binding,
+			comments,
+			block,
+			span,
+		} => Expression::Binding {
+			binding: Binding {
+				lhs: desugar_terms(binding.lhs),
+				annotation: binding.annotation.map(desugar_terms),
+				rhs: desugar_terms(binding.rhs),
+				..binding
+			},
+			comments,
+			block: desugar(block),
+			span,
+		},
+		Expression::Error { .. } => expression,
+	}
+}
+
+fn desugar_terms(terms: Vec<Term>) -> Vec<Term> {
+	terms.into_iter().flat_map(desugar_term).collect()
+}
+
+pub fn desugar_term(term: Term) -> Vec<Term> {
+	match term {
+		Term::Subexpression { terms, span } => vec![Term::Subexpression {
+			terms: desugar_terms(terms),
+			span,
+		}],
+		Term::Group { terms, span } => vec![Term::Group {
+			terms: desugar_terms(terms),
+			span,
+		}],
+		Term::Block { block, span } => vec![Term::Block {
+			block: desugar(block),
+			span,
+		}],
+		Term::Infix {
+			operator,
+			lhs,
+			rhs,
+			span,
+		} => vec![Term::Infix {
+			operator,
+			lhs: desugar_terms(lhs),
+			rhs: desugar_terms(rhs),
+			span,
+		}],
+		Term::Prefix {
+			operator,
+			operand,
+			span,
+		} => vec![Term::Prefix {
+			operator,
+			operand: desugar_terms(operand),
+			span,
+		}],
+		Term::Range {
+			start,
+			end,
+			inclusive,
+			span,
+		} => vec![Term::Range {
+			start: Box::new(single(desugar_term(*start))),
+			end: Box::new(single(desugar_term(*end))),
+			inclusive,
+			span,
+		}],
+		Term::Spread { operand, span } => vec![Term::Spread {
+			operand: Box::new(single(desugar_term(*operand))),
+			span,
+		}],
+		Term::Pipe {
+			direction,
+			lhs,
+			rhs,
+			..
+		} => pipe(direction, desugar_terms(lhs), desugar_terms(rhs)),
+		term => vec![term],
+	}
+}
+
+///
+/// Join terms that replaced a single term
+///
+fn single(mut terms: Vec<Term>) -> Term {
+	match terms.len() {
+		1 => terms.remove(0),
+		_ => wrap(terms),
+	}
+}
+
+///
+/// Wrap terms into a call subexpression unless they are one already
+///
+fn wrap(mut terms: Vec<Term>) -> Term {
+	match terms.as_slice() {
+		[Term::Subexpression { .. }] => terms.remove(0),
+		_ => Term::Subexpression {
+			span: terms_span(&terms).unwrap_or_default(),
+			terms,
+		},
+	}
+}
+
+fn pipe(direction: PipeDirection, mut lhs: Vec<Term>, mut rhs: Vec<Term>) -> Vec<Term> {
+	let placeholder = rhs.iter().position(|x| match x {
+		Term::Identifier { name, .. } => name == "_",
+		_ => false,
+	});
+	match (placeholder, direction) {
+		(Some(index), _) => {
+			rhs[index] = wrap(lhs);
+			rhs
+		}
+		(None, PipeDirection::Forward) => {
+			rhs.push(wrap(lhs));
+			rhs
+		}
+		(None, PipeDirection::Backward) => {
+			lhs.push(wrap(rhs));
+			lhs
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use unicode_segmentation::UnicodeSegmentation;
+
+	use super::super::lexer::*;
+	use super::super::literals::*;
+	use super::*;
+
+	///
+	/// Desugared terms of the first expression, the value for a binding
+	///
+	fn desugar_source(source: &str) -> Vec<Term> {
+		let graphemes = source.graphemes(true).collect::<Vec<_>>();
+		let (tokens, _) = tokenize(&mut Input::new(graphemes.into_iter()), Indentation::Detect);
+		let block = match block(&mut Input::new(tokens.into_iter())) {
+			Output::Ok(block) | Output::Partial { result: block, .. } => desugar(block),
+			output => panic!("parsed to {:?}", output),
+		};
+		match block {
+			Block::Block { expressions, .. } => match expressions.into_iter().next() {
+				Some(Expression::Expression { terms, .. }) => terms,
+				Some(Expression::Binding { binding, .. }) => binding.rhs,
+				expression => panic!("parsed to {:?}", expression),
+			},
+			Block::None => Vec::new(),
+		}
+	}
+
+	///
+	/// Terms as written, with subexpressions in `[...]` and member access without spaces
+	///
+	fn show(terms: &[Term]) -> String {
+		let mut text = String::new();
+		for (index, term) in terms.iter().enumerate() {
+			let access = |x: Option<&Term>| match x {
+				Some(Term::Symbol { text, .. }) => text == ".",
+				_ => false,
+			};
+			if index > 0 && !access(Some(term)) && !access(terms.get(index - 1)) {
+				text.push(' ');
+			}
+			text.push_str(&match term {
+				Term::Identifier { name, .. } => name.clone(),
+				Term::Symbol { text, .. } => text.clone(),
+				Term::Literal {
+					literal: Literal::Integer(value),
+					..
+				} => value.to_string(),
+				Term::Subexpression { terms, .. } => format!("[{}]", show(terms)),
+				term => format!("{:?}", term),
+			});
+		}
+		text
+	}
+
+	#[test]
+	fn forward_pipes() {
+		assert_eq!(
+			show(&desugar_source(
+				"math.greater x y |> math.multiply 2 |> math.greater z"
+			)),
+			"math.greater z [math.multiply 2 [math.greater x y]]"
+		);
+	}
+
+	#[test]
+	fn backward_pipes() {
+		assert_eq!(
+			show(&desugar_source("p : filter math.even <| get_my_numbers")),
+			"filter math.even [get_my_numbers]"
+		);
+	}
+
+	#[test]
+	fn placeholders() {
+		assert_eq!(
+			show(&desugar_source(
+				"graphics.getstruct <| _.x |> math.multiply 2"
+			)),
+			"math.multiply 2 [[graphics.getstruct].x]"
+		);
+	}
+}
@@ -0,0 +1,828 @@
+use std::fmt::{Debug, Display};
+use std::iter::{Iterator, Peekable};
+
+use derive_more::*;
+use itertools::*;
+
+use super::combinators::*;
+use super::types::Output::*;
+use super::types::ParseError::*;
+use super::types::*;
+use super::values::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+	Identifier,
+	Integer,
+	Float,
+	String,
+	Operator,
+	Delimiter,
+	Indent,
+	Dedent,
+	Newline,
+	Comment,
+}
+impl Display for TokenKind {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		let name = match self {
+			Self::Identifier => "identifier",
+			Self::Integer => "integer",
+			Self::Float => "float",
+			Self::String => "string",
+			Self::Operator => "operator",
+			Self::Delimiter => "delimiter",
+			Self::Indent => "<indent>",
+			Self::Dedent => "<dedent>",
+			Self::Newline => "<newline>",
+			Self::Comment => "comment",
+		};
+		write!(f, "{}", name)
+	}
+}
+
+///
+/// Unit of indentation, detected from the first indented line of each file unless given
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indentation {
+	Detect,
+	Tabs,
+	Spaces(usize),
+}
+impl Default for Indentation {
+	fn default() -> Self {
+		Self::Detect
+	}
+}
+impl std::str::FromStr for Indentation {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"detect" => Result::Ok(Self::Detect),
+			"tabs" => Result::Ok(Self::Tabs),
+			_ => match s.parse::<usize>() {
+				Result::Ok(spaces) if spaces > 0 => Result::Ok(Self::Spaces(spaces)),
+				_ => Result::Err(format!("expected `detect`, `tabs` or a number of spaces")),
+			},
+		}
+	}
+}
+impl Indentation {
+	///
+	/// Level of the whitespace starting a line, detecting the unit on the first indented line.
+	/// Returns a description of the whitespace if it doesn't match the unit.
+	///
+	pub fn level(&mut self, indentation: &str) -> Result<usize, String> {
+		let length = indentation.chars().count();
+		let tabs = indentation.chars().all(|c| c == '\t');
+		let spaces = indentation.chars().all(|c| c == ' ');
+		if *self == Self::Detect && length > 0 {
+			if tabs {
+				*self = Self::Tabs;
+			} else if spaces {
+				*self = Self::Spaces(length);
+			}
+		}
+		match *self {
+			_ if length == 0 => Result::Ok(0),
+			Self::Tabs if tabs => Result::Ok(length),
+			Self::Spaces(unit) if spaces && length % unit == 0 => Result::Ok(length / unit),
+			_ if tabs => Result::Err(format!(
+				"{} tab{}",
+				length,
+				if length == 1 { "" } else { "s" }
+			)),
+			_ if spaces => Result::Err(format!(
+				"{} space{}",
+				length,
+				if length == 1 { "" } else { "s" }
+			)),
+			_ => Result::Err(format!("tabs and spaces")),
+		}
+	}
+
+	///
+	/// Description of the indentation accepted by the unit
+	///
+	pub fn expected(self) -> String {
+		match self {
+			Self::Detect => format!("either tabs or spaces"),
+			Self::Tabs => format!("tabs"),
+			Self::Spaces(1) => format!("spaces"),
+			Self::Spaces(unit) => format!("a multiple of {} spaces", unit),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+	pub kind: TokenKind,
+	pub text: String,
+	pub span: Span,
+	pub trivia: String,
+}
+impl Display for Token {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self.kind {
+			TokenKind::Indent | TokenKind::Dedent | TokenKind::Newline => {
+				write!(f, "{}", self.kind)
+			}
+			_ => write!(f, "{}", self.text),
+		}
+	}
+}
+impl AsRef<str> for Token {
+	fn as_ref(&self) -> &str {
+		&self.text
+	}
+}
+impl Advance for Token {
+	fn advance(&self, context: &mut InputContext) {
+		(*context) = context.at(self.span.end);
+	}
+}
+
+pub fn lex_newline<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<String> {
+	map(newline, |x: T| x.to_string())(input)
+}
+
+pub fn lex_comment<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<String> {
+	let hash = literal("#")(input)?;
+	let text = map(
+		all_until(any_char, any_of(&[&discard(peek(newline)), &eof])),
+		|x| x.iter().join(""),
+	)(input)?;
+	Ok(hash + &text)
+}
+
+pub fn lex_identifier<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<String> {
+	let mut text = map(satisfy(|x: &T| is_identifier_start(x), "identifier"), |x| {
+		x.to_string()
+	})(input)?;
+	loop {
+		if let Ok(x) = satisfy(|x: &T| is_identifier_char(x), "identifier")(input) {
+			text.push_str(x.as_ref());
+			continue;
+		}
+		// dashes join identifier parts, as in `compile-time-evaluated`
+		let mut lookahead = input.clone();
+		match (
+			literal("-")(&mut lookahead),
+			satisfy(|x: &T| is_identifier_char(x), "identifier")(&mut lookahead),
+		) {
+			(Ok(dash), Ok(x)) => {
+				text.push_str(&dash);
+				text.push_str(x.as_ref());
+				(*input) = lookahead;
+			}
+			_ => break,
+		}
+	}
+	Ok(text)
+}
+
+pub fn lex_sign<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<String> {
+	any_of(&[&literal("+"), &literal("-")])(input)
+}
+
+///
+/// Lex a digit followed by any digits, separators, radix or exponent letters and suffixes
+///
+pub fn lex_digits<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<String> {
+	let digit = map(satisfy(|x: &T| is_digit(x), "digit"), |x: T| x.to_string())(input)?;
+	let rest = map(
+		zero_or_more(satisfy(|x: &T| is_identifier_char(x), "digit")),
+		|x| x.iter().join(""),
+	)(input)?;
+	Ok(digit + &rest)
+}
+
+///
+/// Lex integer and float literals with optional sign, fraction and signed exponent.
+/// Unlike the old pest grammar, a fraction requires a digit after the dot so `5..9` lexes as a range.
+///
+pub fn lex_number<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<String> {
+	let mut text = match lex_sign(input) {
+		Ok(sign) => sign,
+		_ => String::new(),
+	};
+	text.push_str(&lex_digits(input)?);
+	if radix(&text).is_some() {
+		return Ok(text);
+	}
+	let mut lookahead = input.clone();
+	if let (Ok(dot), Ok(fraction)) = (literal(".")(&mut lookahead), lex_digits(&mut lookahead)) {
+		text.push_str(&dot);
+		text.push_str(&fraction);
+		(*input) = lookahead;
+	}
+	// the exponent letter is consumed with the digits, its sign is not
+	if text.ends_with('e') || text.ends_with('E') {
+		let mut lookahead = input.clone();
+		if let (Ok(sign), Ok(exponent)) = (lex_sign(&mut lookahead), lex_digits(&mut lookahead)) {
+			text.push_str(&sign);
+			text.push_str(&exponent);
+			(*input) = lookahead;
+		}
+	}
+	Ok(text)
+}
+
+///
+/// Whether a lexed number has a fraction, or an `e` after its digits followed only by digits with an optional sign
+///
+pub fn is_float(text: &str) -> bool {
+	if radix(text).is_some() {
+		return false;
+	}
+	let rest = text
+		.trim_start_matches(|c| c == '+' || c == '-')
+		.trim_start_matches(|c: char| c.is_ascii_digit() || c == '_');
+	let mut chars = rest.chars();
+	match chars.next() {
+		Some('.') => true,
+		Some('e') | Some('E') => {
+			let exponent = chars.as_str();
+			let digits = match exponent.chars().next() {
+				Some('+') | Some('-') => &exponent[1..],
+				_ => exponent,
+			};
+			digits.starts_with(|c: char| c.is_ascii_digit())
+				&& digits.chars().all(|c| c.is_ascii_digit() || c == '_')
+		}
+		_ => false,
+	}
+}
+
+///
+/// Radix of an integer literal with a `0x`, `0b` or `0o` prefix
+///
+pub fn radix(text: &str) -> Option<u32> {
+	let text = text.trim_start_matches(|c| c == '+' || c == '-');
+	match text.get(0..2) {
+		Some("0x") | Some("0X") => Some(16),
+		Some("0b") | Some("0B") => Some(2),
+		Some("0o") | Some("0O") => Some(8),
+		_ => None,
+	}
+}
+
+///
+/// Error for a string reaching the end of the line before its closing quote
+///
+fn unterminated<T: InputIterItem>(
+	input: InputRef<T, impl InputIter<T>>,
+	expected: &str,
+) -> Option<ParseError> {
+	if let Ok(x) = peek(newline)(input) {
+		return Some(UnexpectedCharacter {
+			context: input.context,
+			expected: format!("`{}`", expected),
+			found: format!("{}", x),
+		});
+	}
+	if let Ok(_) = eof(input) {
+		return Some(UnexpectedEOF {
+			context: input.context,
+			expected: format!("`{}`", expected),
+		});
+	}
+	None
+}
+
+///
+/// Lex a quoted string, returning a partial result for unterminated strings
+///
+pub fn lex_string<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<String> {
+	let mut text = literal("\"")(input)?;
+	let mut escaped = false;
+	loop {
+		if let Some(error) = unterminated(input, "\"") {
+			return Partial {
+				result: text,
+				error,
+			};
+		}
+		let x = map(any_char, |x: T| x.to_string())(input)?;
+		text.push_str(&x);
+		match x.as_str() {
+			"\"" if !escaped => return Ok(text),
+			"\\" if !escaped => escaped = true,
+			_ => escaped = false,
+		}
+	}
+}
+
+///
+/// Whether `""` is followed by more than whitespace on its line, starting a line string.
+/// Otherwise it is the empty string, which can be written `r""` before more text on the line.
+///
+fn opens_line_string<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> bool {
+	literal("\"\"")(input).is_ok()
+		&& whitespace(input).is_ok()
+		&& !peek(newline)(input).is_ok()
+		&& !eof(input).is_ok()
+}
+
+///
+/// Lex a string spanning the rest of the line after `""`
+///
+pub fn lex_line_string<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<String> {
+	let quotes = literal("\"\"")(input)?;
+	let text = map(
+		all_until(any_char, any_of(&[&discard(peek(newline)), &eof])),
+		|x| x.iter().join(""),
+	)(input)?;
+	Ok(quotes + &text)
+}
+
+///
+/// Lex a raw string `r"..."`, with any number of `#` around the quotes to allow quotes inside
+///
+pub fn lex_raw_string<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<String> {
+	let mut text = literal("r")(input)?;
+	let hashes = map(zero_or_more(literal("#")), |x| x.concat())(input)?;
+	text.push_str(&hashes);
+	text.push_str(&literal("\"")(input)?);
+	let closing = format!("\"{}", hashes);
+	loop {
+		if let Some(error) = unterminated(input, &closing) {
+			return Partial {
+				result: text,
+				error,
+			};
+		}
+		if let Ok(x) = literal(&closing)(input) {
+			text.push_str(&x);
+			return Ok(text);
+		}
+		text.push_str(&map(any_char, |x: T| x.to_string())(input)?);
+	}
+}
+
+pub fn lex_delimiter<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<String> {
+	map(satisfy(|x: &T| is_delimiter(x), "delimiter"), |x| {
+		x.to_string()
+	})(input)
+}
+
+pub fn lex_operator<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<String> {
+	for operator in OPERATORS {
+		if let Ok(x) = literal(operator)(input) {
+			return Ok(x);
+		}
+	}
+	map(satisfy(|x: &T| is_operator_char(x), "operator"), |x| {
+		x.to_string()
+	})(input)
+}
+
+pub fn lex_token<T: InputIterItem>(
+	input: InputRef<T, impl InputIter<T>>,
+) -> Output<(TokenKind, String)> {
+	let x = match input.clone().next() {
+		Ok(x) => x,
+		_ => {
+			return Critical(UnexpectedEOF {
+				context: input.context,
+				expected: format!("token"),
+			})
+		}
+	};
+	let (kind, result) = if is_newline(&x) {
+		(TokenKind::Newline, lex_newline(input))
+	} else if x.as_ref() == "#" {
+		(TokenKind::Comment, lex_comment(input))
+	} else if x.as_ref() == "\"" {
+		match opens_line_string(&mut input.clone()) {
+			true => (TokenKind::String, lex_line_string(input)),
+			false => (TokenKind::String, lex_string(input)),
+		}
+	} else if x.as_ref() == "r" && !lex_raw_string(&mut input.clone()).is_failure() {
+		(TokenKind::String, lex_raw_string(input))
+	} else if is_digit(&x) || is_sign(&x) && lex_number(&mut input.clone()).is_ok() {
+		let result = lex_number(input);
+		let kind = match &result {
+			Ok(text) if is_float(text) => TokenKind::Float,
+			_ => TokenKind::Integer,
+		};
+		(kind, result)
+	} else if is_identifier_start(&x) {
+		(TokenKind::Identifier, lex_identifier(input))
+	} else if is_delimiter(&x) {
+		(TokenKind::Delimiter, lex_delimiter(input))
+	} else if is_operator_char(&x) {
+		(TokenKind::Operator, lex_operator(input))
+	} else {
+		return Error(UnexpectedCharacter {
+			context: input.context,
+			expected: format!("valid character"),
+			found: format!("{}", x),
+		});
+	};
+	result.map(|text| (kind, text))
+}
+
+pub fn whitespace<T: InputIterItem>(input: InputRef<T, impl InputIter<T>>) -> Output<String> {
+	map(zero_or_more(any_of(&[&space, &tab])), |x| x.iter().join(""))(input)
+}
+
+///
+/// Lex the tokens of a single line, including its newline
+///
+pub fn lex_line<T: InputIterItem>(
+	input: InputRef<T, impl InputIter<T>>,
+	indentation: String,
+	errors: &mut Vec<ParseError>,
+) -> Vec<Token> {
+	let mut tokens = Vec::new();
+	let mut trivia = indentation;
+	loop {
+		if let Ok(x) = whitespace(input) {
+			trivia.push_str(&x);
+		}
+		if let Ok(_) = eof(input) {
+			let location = input.context.location();
+			tokens.push(Token {
+				kind: TokenKind::Newline,
+				text: String::new(),
+				span: Span::new(location, location),
+				trivia,
+			});
+			break;
+		}
+		let start = input.context.location();
+		let (kind, text) = match lex_token(input) {
+			Ok(token) => token,
+			Partial { result, error } => {
+				errors.push(error);
+				result
+			}
+			Error(error) | Critical(error) => {
+				errors.push(error);
+				if let Ok(x) = any_char(input) {
+					trivia.push_str(x.as_ref());
+				}
+				continue;
+			}
+		};
+		tokens.push(Token {
+			kind,
+			text,
+			span: Span::new(start, input.context.location()),
+			trivia: std::mem::replace(&mut trivia, String::new()),
+		});
+		if kind == TokenKind::Newline {
+			break;
+		}
+	}
+	tokens
+}
+
+///
+/// Turn source graphemes into tokens, translating indentation into indent and dedent tokens.
+/// Blank and comment-only lines don't change the indentation and are emitted after the
+/// indentation tokens of the following line. A line not indented with the unit keeps the
+/// indentation of the line before it.
+///
+pub fn tokenize<T: InputIterItem>(
+	input: InputRef<T, impl InputIter<T>>,
+	mut unit: Indentation,
+) -> (Vec<Token>, Vec<ParseError>) {
+	let mut tokens = Vec::new();
+	let mut errors = Vec::new();
+	let mut pending = Vec::new();
+	let mut levels = vec![0];
+	while let Error(_) = eof(input) {
+		let start = input.context.location();
+		let indentation = match whitespace(input) {
+			Ok(x) => x,
+			_ => String::new(),
+		};
+		let mut line = lex_line(input, indentation.clone(), &mut errors);
+		if line
+			.iter()
+			.all(|t| t.kind == TokenKind::Newline || t.kind == TokenKind::Comment)
+		{
+			pending.append(&mut line);
+			continue;
+		}
+		let location = line[0].span.start;
+		let expected = unit.expected();
+		let level = match unit.level(&indentation) {
+			Result::Ok(level) => level,
+			Result::Err(found) => {
+				errors.push(InconsistentIndentation {
+					context: InputContext::default().at(start),
+					expected,
+					found,
+				});
+				levels[levels.len() - 1]
+			}
+		};
+		let marker = |kind| Token {
+			kind,
+			text: String::new(),
+			span: Span::new(location, location),
+			trivia: String::new(),
+		};
+		while level < levels[levels.len() - 1] {
+			levels.pop();
+			tokens.push(marker(TokenKind::Dedent));
+		}
+		if level > levels[levels.len() - 1] {
+			levels.push(level);
+			tokens.push(marker(TokenKind::Indent));
+		}
+		tokens.append(&mut pending);
+		tokens.append(&mut line);
+	}
+	let location = input.context.location();
+	for _ in 1..levels.len() {
+		tokens.push(Token {
+			kind: TokenKind::Dedent,
+			text: String::new(),
+			span: Span::new(location, location),
+			trivia: String::new(),
+		});
+	}
+	tokens.append(&mut pending);
+	(tokens, errors)
+}
+
+#[cfg(test)]
+mod tests {
+	use unicode_segmentation::UnicodeSegmentation;
+
+	use super::*;
+
+	fn lex(source: &str) -> (Vec<(TokenKind, String)>, Vec<ParseError>) {
+		let graphemes = source.graphemes(true).collect::<Vec<_>>();
+		let (tokens, errors) =
+			tokenize(&mut Input::new(graphemes.into_iter()), Indentation::Detect);
+		let tokens = tokens
+			.into_iter()
+			.filter(|x| x.kind != TokenKind::Newline)
+			.map(|x| (x.kind, x.text))
+			.collect();
+		(tokens, errors)
+	}
+
+	fn token(kind: TokenKind, text: &str) -> (TokenKind, String) {
+		(kind, text.to_string())
+	}
+
+	fn kinds(source: &str) -> Vec<TokenKind> {
+		lex(source).0.into_iter().map(|x| x.0).collect()
+	}
+
+	#[test]
+	fn identifiers_and_operators() {
+		use TokenKind::*;
+		let (tokens, errors) = lex("compile-time-evaluated :: a...b:=[c] x- y");
+		assert!(errors.is_empty());
+		assert_eq!(
+			tokens,
+			vec![
+				token(Identifier, "compile-time-evaluated"),
+				token(Operator, "::"),
+				token(Identifier, "a"),
+				token(Operator, "..."),
+				token(Identifier, "b"),
+				token(Operator, ":="),
+				token(Delimiter, "["),
+				token(Identifier, "c"),
+				token(Delimiter, "]"),
+				token(Identifier, "x"),
+				token(Operator, "-"),
+				token(Identifier, "y"),
+			]
+		);
+	}
+
+	#[test]
+	fn signs() {
+		use TokenKind::*;
+		assert_eq!(
+			lex("a - 1").0,
+			vec![
+				token(Identifier, "a"),
+				token(Operator, "-"),
+				token(Integer, "1")
+			]
+		);
+		assert_eq!(
+			lex("a -1").0,
+			vec![token(Identifier, "a"), token(Integer, "-1")]
+		);
+		assert_eq!(
+			lex("+ x").0,
+			vec![token(Operator, "+"), token(Identifier, "x")]
+		);
+	}
+
+	#[test]
+	fn comments_and_trivia() {
+		let graphemes = "a  # note\n\tb".graphemes(true).collect::<Vec<_>>();
+		let (tokens, errors) =
+			tokenize(&mut Input::new(graphemes.into_iter()), Indentation::Detect);
+		assert!(errors.is_empty());
+		let comment = &tokens[1];
+		assert_eq!(comment.kind, TokenKind::Comment);
+		assert_eq!(comment.text, "# note");
+		assert_eq!(comment.trivia, "  ");
+		let b = tokens.iter().find(|x| x.text == "b").unwrap();
+		assert_eq!(b.trivia, "\t");
+		assert_eq!(b.span.start.line, 1);
+		assert_eq!(b.span.start.column, 1);
+	}
+
+	#[test]
+	fn comment_lines() {
+		use TokenKind::*;
+		let (tokens, errors) = lex("a # x\n# y\n\t# z\nb \"# w\"");
+		assert!(errors.is_empty());
+		assert_eq!(
+			tokens,
+			vec![
+				token(Identifier, "a"),
+				token(Comment, "# x"),
+				token(Comment, "# y"),
+				token(Comment, "# z"),
+				token(Identifier, "b"),
+				token(String, "\"# w\""),
+			]
+		);
+	}
+
+	#[test]
+	fn indentation() {
+		use TokenKind::*;
+		assert_eq!(
+			kinds("a\n\tb\n\t\tc\n\n\t# comment\nd"),
+			vec![
+				Identifier, Indent, Identifier, Indent, Identifier, Dedent, Dedent, Comment,
+				Identifier
+			]
+		);
+		assert_eq!(
+			kinds("a\n    b\n        c\n    d"),
+			vec![Identifier, Indent, Identifier, Indent, Identifier, Dedent, Identifier, Dedent]
+		);
+	}
+
+	#[test]
+	fn inconsistent_indentation() {
+		let (_, errors) = lex("a\n\tb\n    c\n\t d");
+		let found = errors
+			.into_iter()
+			.map(|x| match x {
+				InconsistentIndentation { context, found, .. } => (context.line, found),
+				error => panic!("unexpected error {:?}", error),
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(
+			found,
+			vec![(2, format!("4 spaces")), (3, format!("tabs and spaces"))]
+		);
+		let graphemes = "a\n  b".graphemes(true).collect::<Vec<_>>();
+		let (_, errors) = tokenize(
+			&mut Input::new(graphemes.into_iter()),
+			Indentation::Spaces(4),
+		);
+		assert_eq!(errors.len(), 1);
+	}
+
+	///
+	/// Token kinds in a fixed indentation mode, and the indentation found where it is inconsistent
+	///
+	fn indented(source: &str, indentation: Indentation) -> (Vec<TokenKind>, Vec<String>) {
+		let graphemes = source.graphemes(true).collect::<Vec<_>>();
+		let (tokens, errors) = tokenize(&mut Input::new(graphemes.into_iter()), indentation);
+		let kinds = tokens.into_iter().map(|x| x.kind);
+		let found = errors.into_iter().map(|x| match x {
+			InconsistentIndentation { found, .. } => found,
+			error => panic!("unexpected error {:?}", error),
+		});
+		(
+			kinds.filter(|x| *x != TokenKind::Newline).collect(),
+			found.collect(),
+		)
+	}
+
+	#[test]
+	fn indentation_modes() {
+		use TokenKind::*;
+		assert_eq!("detect".parse(), Result::Ok(Indentation::Detect));
+		assert_eq!("tabs".parse(), Result::Ok(Indentation::Tabs));
+		assert_eq!("4".parse(), Result::Ok(Indentation::Spaces(4)));
+		for text in &["0", "-2", "spaces", ""] {
+			assert!(text.parse::<Indentation>().is_err(), "{}", text);
+		}
+
+		let nested = vec![
+			Identifier, Indent, Identifier, Indent, Identifier, Dedent, Dedent,
+		];
+		let (kinds, found) = indented("a\n\tb\n\t\tc", Indentation::Tabs);
+		assert_eq!((kinds, found), (nested.clone(), vec![]));
+		let (_, found) = indented("a\n    b", Indentation::Tabs);
+		assert_eq!(found, vec![format!("4 spaces")]);
+
+		let (kinds, found) = indented("a\n  b\n    c", Indentation::Spaces(2));
+		assert_eq!((kinds, found), (nested, vec![]));
+		let (_, found) = indented("a\n\tb\n   c", Indentation::Spaces(2));
+		assert_eq!(found, vec![format!("1 tab"), format!("3 spaces")]);
+	}
+
+	#[test]
+	fn strings() {
+		use TokenKind::*;
+		let (tokens, errors) = lex(r##"a "b \" c" r#"d "e""# ""f "g" # h"##);
+		assert!(errors.is_empty());
+		assert_eq!(
+			tokens,
+			vec![
+				token(Identifier, "a"),
+				token(String, r#""b \" c""#),
+				token(String, r##"r#"d "e""#"##),
+				token(String, r#"""f "g" # h"#),
+			]
+		);
+		let (tokens, errors) = lex("a \"\" \nx \"\"\nf r\"\" b");
+		assert!(errors.is_empty());
+		assert_eq!(
+			tokens,
+			vec![
+				token(Identifier, "a"),
+				token(String, "\"\""),
+				token(Identifier, "x"),
+				token(String, "\"\""),
+				token(Identifier, "f"),
+				token(String, "r\"\""),
+				token(Identifier, "b"),
+			]
+		);
+	}
+
+	#[test]
+	fn unterminated_strings() {
+		let (tokens, errors) = lex("\"abc\nx");
+		assert_eq!(
+			tokens,
+			vec![
+				token(TokenKind::String, "\"abc"),
+				token(TokenKind::Identifier, "x")
+			]
+		);
+		match errors.as_slice() {
+			[UnexpectedCharacter { expected, .. }] => assert_eq!(expected, "`\"`"),
+			errors => panic!("unexpected errors {:?}", errors),
+		}
+		let (_, errors) = lex("r#\"abc\"");
+		match errors.as_slice() {
+			[UnexpectedEOF { expected, .. }] => assert_eq!(expected, "`\"#`"),
+			errors => panic!("unexpected errors {:?}", errors),
+		}
+	}
+
+	#[test]
+	fn floats() {
+		for text in &["1.5", "1e5", "1E-5", "+2e+3", "1_000.0"] {
+			assert!(is_float(text), "{}", text);
+		}
+		for text in &[
+			"15", "10sec", "1e", "1ex", "1e+", "0x1e", "-0b1e1", "1e5x", "1e+-5",
+		] {
+			assert!(!is_float(text), "{}", text);
+		}
+	}
+
+	#[test]
+	fn numbers() {
+		use TokenKind::*;
+		let (tokens, errors) = lex("1.5 1e5 2e-3 10sec 1e5x 0x1e -7");
+		assert!(errors.is_empty());
+		assert_eq!(
+			tokens,
+			vec![
+				token(Float, "1.5"),
+				token(Float, "1e5"),
+				token(Float, "2e-3"),
+				token(Integer, "10sec"),
+				token(Integer, "1e5x"),
+				token(Integer, "0x1e"),
+				token(Integer, "-7"),
+			]
+		);
+		let (tokens, _) = lex("5..9");
+		assert_eq!(
+			tokens,
+			vec![
+				token(Integer, "5"),
+				token(Operator, ".."),
+				token(Integer, "9")
+			]
+		);
+	}
+}
@@ -0,0 +1,354 @@
+use std::fmt::{Debug, Display};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::combinators::*;
+use super::lexer::*;
+use super::syntax::*;
+use super::types::Output::*;
+use super::types::ParseError::*;
+use super::types::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+	Integer(i128),
+	Float(f64),
+	String(String),
+}
+
+///
+/// Integer literals have to fit into 64 bits, signed or unsigned
+///
+pub const INTEGER_MIN: i128 = i64::min_value() as i128;
+pub const INTEGER_MAX: i128 = u64::max_value() as i128;
+
+fn radix_name(radix: u32) -> &'static str {
+	match radix {
+		2 => "binary",
+		8 => "octal",
+		16 => "hexadecimal",
+		_ => "decimal",
+	}
+}
+
+pub fn decode_integer(text: &str, context: InputContext) -> Result<i128, ParseError> {
+	let negative = text.starts_with('-');
+	let unsigned = text.trim_start_matches(|c| c == '+' || c == '-');
+	let (radix, digits) = match radix(unsigned) {
+		Some(radix) => (radix, &unsigned[2..]),
+		None => (10, unsigned),
+	};
+	let invalid = |reason: String| InvalidLiteral {
+		context,
+		literal: text.to_string(),
+		reason,
+	};
+	if digits.trim_start_matches('_').is_empty() {
+		return Err(invalid(format!("missing digits after the radix prefix")));
+	}
+	let mut value: u128 = 0;
+	let mut overflow = false;
+	for c in digits.chars().filter(|c| *c != '_') {
+		let digit = c.to_digit(radix).ok_or_else(|| {
+			invalid(format!(
+				"invalid digit `{}` in {} literal",
+				c,
+				radix_name(radix)
+			))
+		})?;
+		match value
+			.checked_mul(radix as u128)
+			.and_then(|v| v.checked_add(digit as u128))
+		{
+			Some(v) => value = v,
+			None => overflow = true,
+		}
+	}
+	let value = match (overflow, negative) {
+		(false, true) if value <= -INTEGER_MIN as u128 => -(value as i128),
+		(false, false) if value <= INTEGER_MAX as u128 => value as i128,
+		_ => {
+			return Err(LiteralOverflow {
+				context,
+				literal: text.to_string(),
+				bounds: format!("{}..={}", INTEGER_MIN, INTEGER_MAX),
+			})
+		}
+	};
+	Result::Ok(value)
+}
+
+pub fn decode_float(text: &str, context: InputContext) -> Result<f64, ParseError> {
+	let digits = text.replace('_', "");
+	let invalid = |reason: String| InvalidLiteral {
+		context,
+		literal: text.to_string(),
+		reason,
+	};
+	if let Some(c) = digits
+		.chars()
+		.find(|c| !c.is_ascii_digit() && !['+', '-', '.', 'e', 'E'].contains(c))
+	{
+		return Err(invalid(format!(
+			"invalid character `{}` in float literal",
+			c
+		)));
+	}
+	let value = digits
+		.parse::<f64>()
+		.map_err(|_| invalid(format!("malformed float literal")))?;
+	if value.is_infinite() {
+		return Err(LiteralOverflow {
+			context,
+			literal: text.to_string(),
+			bounds: format!("64-bit floats"),
+		});
+	}
+	Result::Ok(value)
+}
+
+///
+/// Decode an escape sequence starting after the backslash, returning the character and the length of the sequence
+///
+fn decode_escape(sequence: &str) -> Result<(char, usize), String> {
+	let mut chars = sequence.chars();
+	let c = match chars.next() {
+		Some(c) => c,
+		None => return Err(format!("missing escaped character")),
+	};
+	let decoded = match c {
+		'n' => '\n',
+		'r' => '\r',
+		't' => '\t',
+		'0' => '\0',
+		'\\' | '"' | '\'' => c,
+		'u' => {
+			let digits = match (sequence.get(1..2), sequence.find('}')) {
+				(Some("{"), Some(end)) => &sequence[2..end],
+				_ => return Err(format!("unicode escapes are written as `\\u{{...}}`")),
+			};
+			if digits.is_empty() || digits.len() > 6 {
+				return Err(format!("unicode escapes take 1 to 6 hexadecimal digits"));
+			}
+			let value = u32::from_str_radix(digits, 16).map_err(|_| {
+				format!("invalid hexadecimal digits `{}` in unicode escape", digits)
+			})?;
+			let decoded = std::char::from_u32(value)
+				.ok_or_else(|| format!("`{:X}` is not a unicode scalar value", value))?;
+			return Result::Ok((decoded, digits.len() + 3));
+		}
+		_ => return Err(format!("unknown escape `\\{}`", c)),
+	};
+	Result::Ok((decoded, c.len_utf8()))
+}
+
+///
+/// Decode a string literal token: `"..."` with escapes, `""...` spanning the rest of the line, or raw `r#"..."#`.
+/// Unterminated strings are reported by the lexer and decoded up to the end of the line.
+///
+pub fn decode_string(
+	text: &str,
+	start: Location,
+	context: InputContext,
+) -> Result<String, ParseError> {
+	if text.starts_with("\"\"") {
+		return Result::Ok(text[2..].to_string());
+	}
+	if text.starts_with('r') {
+		let hashes = text[1..].chars().take_while(|c| *c == '#').count();
+		let content = &text[hashes + 2..];
+		let closing = format!("\"{}", "#".repeat(hashes));
+		return Result::Ok(content.trim_end_matches(closing.as_str()).to_string());
+	}
+	let mut result = String::new();
+	let mut index = 1;
+	while index < text.len() {
+		let c = text[index..].chars().next().unwrap_or_default();
+		match c {
+			'"' => break,
+			'\\' => match decode_escape(&text[index + 1..]) {
+				Result::Ok((decoded, length)) => {
+					result.push(decoded);
+					index += length + 1;
+				}
+				Result::Err(reason) => {
+					let location = Location {
+						offset: start.offset + index,
+						line: start.line,
+						column: start.column + text[..index].graphemes(true).count(),
+					};
+					return Err(InvalidLiteral {
+						context: context.at(location),
+						literal: text.to_string(),
+						reason,
+					});
+				}
+			},
+			_ => {
+				result.push(c);
+				index += c.len_utf8();
+			}
+		}
+	}
+	Result::Ok(result)
+}
+
+///
+/// Parse a literal token into a literal term, recording an error term for invalid literals
+///
+pub fn literal_term(input: InputRef<Token, impl InputIter<Token>>) -> Output<Term> {
+	let x = any_of(&[
+		&token(TokenKind::Integer),
+		&token(TokenKind::Float),
+		&token(TokenKind::String),
+	])(input)?;
+	let context = input.context.at(x.span.start);
+	let literal = match x.kind {
+		TokenKind::String => decode_string(&x.text, x.span.start, context).map(Literal::String),
+		TokenKind::Float => decode_float(&x.text, context).map(Literal::Float),
+		_ => decode_integer(&x.text, context).map(Literal::Integer),
+	};
+	match literal {
+		Result::Ok(literal) => Ok(Term::Literal {
+			literal,
+			span: x.span,
+		}),
+		Result::Err(error) => Partial {
+			result: Term::Error {
+				error: error.clone(),
+				span: x.span,
+			},
+			error,
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn integer(text: &str) -> Result<i128, ParseError> {
+		decode_integer(text, InputContext::default())
+	}
+
+	fn reason(error: ParseError) -> String {
+		match error {
+			InvalidLiteral { reason, .. } => reason,
+			LiteralOverflow { bounds, .. } => bounds,
+			error => panic!("unexpected error {:?}", error),
+		}
+	}
+
+	#[test]
+	fn integers() {
+		assert_eq!(integer("1_000").unwrap(), 1000);
+		assert_eq!(integer("+42").unwrap(), 42);
+		assert_eq!(integer("0x_ff").unwrap(), 255);
+		assert_eq!(integer("-0b101").unwrap(), -5);
+		assert_eq!(integer("0o17").unwrap(), 15);
+		assert_eq!(
+			reason(integer("0b102").unwrap_err()),
+			"invalid digit `2` in binary literal"
+		);
+		assert_eq!(
+			reason(integer("10sec").unwrap_err()),
+			"invalid digit `s` in decimal literal"
+		);
+		assert_eq!(
+			reason(integer("0x_").unwrap_err()),
+			"missing digits after the radix prefix"
+		);
+	}
+
+	#[test]
+	fn integer_bounds() {
+		assert_eq!(integer("-9223372036854775808").unwrap(), INTEGER_MIN);
+		assert_eq!(integer("18446744073709551615").unwrap(), INTEGER_MAX);
+		assert_eq!(integer("0xffff_ffff_ffff_ffff").unwrap(), INTEGER_MAX);
+		for text in &[
+			"-9223372036854775809",
+			"18446744073709551616",
+			"0x1_0000_0000_0000_0000",
+			"340282366920938463463374607431768211456",
+		] {
+			match integer(text) {
+				Err(LiteralOverflow { .. }) => {}
+				result => panic!("{} decoded to {:?}", text, result),
+			}
+		}
+	}
+
+	fn string(text: &str) -> Result<String, ParseError> {
+		decode_string(text, Location::default(), InputContext::default())
+	}
+
+	#[test]
+	fn strings() {
+		assert_eq!(
+			string(r#""a\tb\n\\ \" \' \0""#).unwrap(),
+			"a\tb\n\\ \" ' \0"
+		);
+		assert_eq!(
+			string(r#""\u{41}\u{1F600}\u{10FFFF}""#).unwrap(),
+			"A\u{1F600}\u{10FFFF}"
+		);
+		assert_eq!(
+			string(r#"""rest "of" \n the line"#).unwrap(),
+			r#"rest "of" \n the line"#
+		);
+		assert_eq!(string(r#"r"a\nb""#).unwrap(), r#"a\nb"#);
+		assert_eq!(string(r##"r#"a "quoted" b"#"##).unwrap(), r#"a "quoted" b"#);
+		assert_eq!(string(r#""unterminated"#).unwrap(), "unterminated");
+	}
+
+	#[test]
+	fn escapes() {
+		assert_eq!(
+			reason(string(r#""\u{}""#).unwrap_err()),
+			"unicode escapes take 1 to 6 hexadecimal digits"
+		);
+		assert_eq!(
+			reason(string(r#""\u{1000000}""#).unwrap_err()),
+			"unicode escapes take 1 to 6 hexadecimal digits"
+		);
+		assert_eq!(
+			reason(string(r#""\u41""#).unwrap_err()),
+			"unicode escapes are written as `\\u{...}`"
+		);
+		assert_eq!(
+			reason(string(r#""\u{D800}""#).unwrap_err()),
+			"`D800` is not a unicode scalar value"
+		);
+		assert_eq!(
+			reason(string(r#""\u{xyz}""#).unwrap_err()),
+			"invalid hexadecimal digits `xyz` in unicode escape"
+		);
+		assert_eq!(
+			reason(string(r#""\q""#).unwrap_err()),
+			"unknown escape `\\q`"
+		);
+		match string(r#""äb\q""#) {
+			Err(InvalidLiteral { context, .. }) => {
+				assert_eq!(context.offset, 4);
+				assert_eq!(context.position, 3);
+			}
+			result => panic!("decoded to {:?}", result),
+		}
+	}
+
+	#[test]
+	fn floats() {
+		let float = |text| decode_float(text, InputContext::default());
+		assert_eq!(float("1.5").unwrap(), 1.5);
+		assert_eq!(float("-2e-3").unwrap(), -0.002);
+		assert_eq!(float("1_000.25").unwrap(), 1000.25);
+		match float("1e400") {
+			Err(LiteralOverflow { .. }) => {}
+			result => panic!("decoded to {:?}", result),
+		}
+		assert_eq!(
+			reason(float("1.5sec").unwrap_err()),
+			"invalid character `s` in float literal"
+		);
+	}
+}
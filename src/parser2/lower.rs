@@ -0,0 +1,2080 @@
+use std::collections::HashMap;
+use std::iter::Peekable;
+
+use super::ast;
+use super::ast::{ExpressionKind, Fundamental, Identifier, Node, Passing, Pattern, Type};
+use super::desugar::*;
+use super::literals::*;
+use super::syntax::*;
+use super::types::ParseError::*;
+use super::types::*;
+
+///
+/// Lower a parsed block into the typed syntax tree, returning the errors found while lowering
+///
+pub fn lower(block: Block) -> (ast::Block, Vec<ParseError>) {
+	let mut lowerer = Lowerer {
+		errors: Vec::new(),
+		aliases: HashMap::new(),
+	};
+	let block = lowerer.block(block);
+	(block, lowerer.errors)
+}
+
+struct Lowerer {
+	errors: Vec<ParseError>,
+	///
+	/// Tags defined by `@alias`, usable after their definition until the end of the enclosing block
+	///
+	aliases: HashMap<String, ast::Tag>,
+}
+impl Lowerer {
+	fn block(&mut self, block: Block) -> ast::Block {
+		match block {
+			Block::Block { expressions, span } => {
+				let aliases = self.aliases.clone();
+				let mut lowered = Vec::new();
+				let mut tags = Vec::new();
+				for mut expression in expressions {
+					let trailing = match &mut expression {
+						Expression::Expression { terms, .. } => self.tags(terms),
+						Expression::Binding { binding, .. } => self.tags(&mut binding.rhs),
+						Expression::Error { .. } => Vec::new(),
+					};
+					let compile_time = match &mut expression {
+						Expression::Expression { terms, .. } => compile_time(terms),
+						_ => false,
+					};
+					match &expression {
+						Expression::Expression {
+							terms,
+							block: Block::None,
+							..
+						} if terms.is_empty() => tags.extend(trailing),
+						_ => {
+							let mut expression = self.expression(expression);
+							expression.tags = tags.drain(..).chain(trailing).collect();
+							expression.compile_time = compile_time;
+							lowered.push(expression);
+						}
+					}
+				}
+				for tag in tags {
+					let reason = format!("expected an expression after the tag");
+					self.invalid_tag(&tag.name.name, tag.span, reason);
+				}
+				self.aliases = aliases;
+				ast::Block {
+					expressions: lowered,
+					span,
+				}
+			}
+			Block::None => ast::Block::default(),
+		}
+	}
+
+	fn optional_block(&mut self, block: Block) -> Option<ast::Block> {
+		match block {
+			Block::None => None,
+			block => Some(self.block(block)),
+		}
+	}
+
+	fn expression(&mut self, expression: Expression) -> ast::Expression {
+		match expression {
+			Expression::Expression {
+				mut terms,
+				comments,
+				block,
+				span,
+			} if is_keyword(terms.first(), "im") => {
+				let keyword = terms.remove(0).span();
+				let module = self.node(terms, block).unwrap_or_else(|| {
+					let reason = format!("expected the module to import");
+					let error = self.invalid("im", keyword, reason);
+					Node::Error {
+						error,
+						span: keyword,
+					}
+				});
+				ast::Expression {
+					kind: ExpressionKind::Import(ast::Import { module }),
+					comments,
+					tags: Vec::new(),
+					compile_time: false,
+					span,
+				}
+			}
+			Expression::Expression {
+				terms,
+				mut comments,
+				mut block,
+				span,
+			} => {
+				let continued = continued_comments(&terms, &mut block);
+				comments.dangling.splice(0..0, continued);
+				let node = self.node(terms, block).unwrap_or(Node::Tuple {
+					elements: Vec::new(),
+					span,
+				});
+				ast::Expression {
+					kind: ExpressionKind::Node(node),
+					comments,
+					tags: Vec::new(),
+					compile_time: false,
+					span,
+				}
+			}
+			Expression::Binding {
+				binding,
+				comments,
+				block: block @ Block::Block { .. },
+				span,
+			} if binding.kind == BindingKind::Add => {
+				let reason = format!("expected the implementing type after `:+`");
+				let implementation = ast::Implementation {
+					trait_type: self.annotation(binding.lhs),
+					target: self.required_type(":+", binding.rhs, binding.operator, reason),
+					block: self.block(block),
+				};
+				ast::Expression {
+					kind: ExpressionKind::Implementation(implementation),
+					comments,
+					tags: Vec::new(),
+					compile_time: false,
+					span,
+				}
+			}
+			Expression::Binding {
+				mut binding,
+				mut comments,
+				mut block,
+				span,
+			} => {
+				let compile_time = compile_time(&mut binding.rhs);
+				let continued = continued_comments(&binding.rhs, &mut block);
+				comments.dangling.splice(0..0, continued);
+				let target = self.pattern(binding.lhs);
+				let mut annotation = binding.annotation.map(|x| self.annotation(x));
+				let mut value = self.node(binding.rhs, block);
+				// `x : A | B` declares `x` of the sum type rather than binding a value
+				if let (BindingKind::Declare, None, Some(Node::Infix { operator, .. })) =
+					(binding.kind, &annotation, &value)
+				{
+					if operator == "|" {
+						annotation = value.take().map(node_type);
+					}
+				}
+				let binding = ast::Binding {
+					kind: binding.kind,
+					target,
+					annotation,
+					value,
+					compile_time: compile_time || binding.kind == BindingKind::AssignCompileTime,
+				};
+				ast::Expression {
+					kind: ExpressionKind::Binding(binding),
+					comments,
+					tags: Vec::new(),
+					compile_time: false,
+					span,
+				}
+			}
+			Expression::Error { error, span } => ast::Expression {
+				kind: ExpressionKind::Error(error),
+				comments: Comments::default(),
+				tags: Vec::new(),
+				compile_time: false,
+				span,
+			},
+		}
+	}
+
+	///
+	/// Split the `@name arguments` tags trailing the terms off, expanding aliases and recording `@alias` definitions
+	///
+	fn tags(&mut self, terms: &mut Vec<Term>) -> Vec<ast::Tag> {
+		let start = match terms.iter().position(|x| is_symbol(Some(x), "@")) {
+			Some(start) => start,
+			None => return Vec::new(),
+		};
+		let mut tags = Vec::new();
+		let mut terms = terms.split_off(start).into_iter().peekable();
+		while let Some(symbol) = terms.next() {
+			let mut arguments = Vec::new();
+			while terms.peek().map_or(false, |x| !is_symbol(Some(x), "@")) {
+				arguments.extend(terms.next());
+			}
+			let span = arguments
+				.last()
+				.map_or(symbol.span(), |x| symbol.span().to(x.span()));
+			let name = match arguments.first() {
+				Some(Term::Identifier { span, .. })
+					if symbol.span().end.offset == span.start.offset =>
+				{
+					identifier(arguments.remove(0))
+				}
+				_ => None,
+			};
+			match name {
+				Some(name) if name.name == "alias" => self.alias(arguments, span),
+				Some(name) => tags.push(self.tag(name, arguments, span)),
+				None => {
+					let reason = format!("expected the tag name directly after `@`");
+					self.invalid_tag("", span, reason);
+				}
+			}
+		}
+		tags
+	}
+
+	///
+	/// Split the tags off member or variant lines, a line of tags and its comments apply to the following line.
+	/// Aliases defined in the lines are local to the declaration.
+	///
+	fn tagged_lines(&mut self, declaration: &str, lines: Vec<Line>) -> Vec<Line> {
+		let aliases = self.aliases.clone();
+		let mut tagged = Vec::new();
+		let mut tags = Vec::new();
+		let mut comments = Vec::new();
+		for mut line in lines {
+			let trailing = self.tags(&mut line.terms);
+			match &line.block {
+				Block::None if line.terms.is_empty() => {
+					tags.extend(trailing);
+					comments.extend(line.comments.leading);
+					comments.extend(line.comments.trailing);
+				}
+				_ => {
+					line.tags = tags.drain(..).chain(trailing).collect();
+					line.comments.leading.splice(0..0, comments.drain(..));
+					tagged.push(line);
+				}
+			}
+		}
+		for tag in tags {
+			let reason = match declaration {
+				"en" => format!("expected a variant after the tag"),
+				_ => format!("expected a member after the tag"),
+			};
+			self.invalid_tag(&tag.name.name, tag.span, reason);
+		}
+		self.aliases = aliases;
+		tagged
+	}
+
+	fn tag(&mut self, name: Identifier, arguments: Vec<Term>, span: Span) -> ast::Tag {
+		let mut arguments = self.nodes(arguments, Block::None);
+		match self.aliases.get(&name.name) {
+			Some(alias) => {
+				let mut tag = alias.clone();
+				tag.name.span = name.span;
+				tag.arguments.append(&mut arguments);
+				tag.span = span;
+				tag
+			}
+			None => ast::Tag {
+				name,
+				arguments,
+				span,
+			},
+		}
+	}
+
+	///
+	/// `@alias name [tag arguments]`, the aliased tag is expanded when the alias is defined
+	///
+	fn alias(&mut self, arguments: Vec<Term>, span: Span) {
+		let mut arguments = arguments.into_iter();
+		let name = arguments.next().and_then(identifier);
+		let target = match arguments.next() {
+			Some(Term::Subexpression { mut terms, .. }) if is_name(terms.first()) => {
+				let target = identifier(terms.remove(0));
+				target.map(|x| (x, terms))
+			}
+			_ => None,
+		};
+		match (name, target, arguments.next()) {
+			(Some(name), Some((target, terms)), None) => {
+				let tag = self.tag(target, terms, span);
+				self.aliases.insert(name.name, tag);
+			}
+			_ => {
+				let reason = format!("expected the alias name and the aliased tag in `[...]`");
+				self.invalid_tag("alias", span, reason);
+			}
+		}
+	}
+
+	///
+	/// Lower juxtaposed terms into a call of the first term. A fundamental takes the following block,
+	/// otherwise each line of the block continues the arguments, starting with the function if there are no terms.
+	/// A block containing statements is passed as the last argument instead.
+	///
+	fn node(&mut self, terms: Vec<Term>, block: Block) -> Option<Node> {
+		let mut nodes = match continuation(&terms, block) {
+			Result::Ok(lines) => {
+				let mut nodes = self.nodes(terms, Block::None);
+				for (terms, block) in lines {
+					if nodes.is_empty() {
+						nodes = self.nodes(terms, block);
+					} else {
+						nodes.extend(self.nodes(terms, block));
+					}
+				}
+				nodes
+			}
+			Result::Err(block) => self.nodes(terms, block),
+		};
+		match nodes.len() {
+			0 | 1 => nodes.pop(),
+			_ => {
+				let function = nodes.remove(0);
+				let span = function.span().to(nodes[nodes.len() - 1].span());
+				Some(Node::Call {
+					function: Box::new(function),
+					arguments: nodes,
+					span,
+				})
+			}
+		}
+	}
+
+	///
+	/// Lower terms joining member access, a fundamental takes the remaining terms and the block as its arguments
+	///
+	fn nodes(&mut self, terms: Vec<Term>, mut block: Block) -> Vec<Node> {
+		let mut nodes: Vec<Node> = Vec::new();
+		let mut terms = terms.into_iter().peekable();
+		let adjacent = |a: Span, b: Span| a.end.offset == b.start.offset;
+		let mut marked: Option<Span> = None;
+		let mut prefixes: Vec<(usize, String, Span)> = Vec::new();
+		while let Some(term) = terms.next() {
+			if let Term::Identifier { name, span } = &term {
+				if let Some(kind) = FundamentalKind::from_keyword(name) {
+					let block = std::mem::replace(&mut block, Block::None);
+					let mut fundamental =
+						self.fundamental(kind, *span, terms.by_ref().collect(), block);
+					match (&mut fundamental, marked.take()) {
+						(Fundamental::Function(function), Some(symbol)) => {
+							function.compile_time = true;
+							function.span = symbol.to(function.span);
+						}
+						(Fundamental::Match(_), Some(symbol)) => {
+							let reason = format!("only a `mt` starting the expression or the value can be evaluated at compile time");
+							self.misplaced(symbol, reason);
+						}
+						(_, Some(symbol)) => {
+							let reason = format!(
+								"a `{}` declaration cannot be evaluated at compile time",
+								kind.keyword()
+							);
+							self.misplaced(symbol, reason);
+						}
+						(_, None) => {}
+					}
+					nodes.push(Node::Fundamental(fundamental));
+					break;
+				}
+			}
+			if let Term::Symbol { text, span } = &term {
+				let marks = match terms.peek() {
+					Some(Term::Identifier { name, span: next })
+						if text == "!" && adjacent(*span, *next) =>
+					{
+						FundamentalKind::from_keyword(name).is_some()
+					}
+					_ => false,
+				};
+				if marks {
+					marked = Some(*span);
+					continue;
+				}
+				if is_prefix(text) {
+					let operand = terms.peek().filter(|x| adjacent(*span, x.span()));
+					match operand {
+						Some(Term::Symbol { text: next, .. }) if is_prefix(next) => {
+							let reason = format!("cannot be stacked on `{}`", next);
+							self.invalid_prefix(text, *span, reason);
+							continue;
+						}
+						Some(operand) => {
+							let span = span.to(operand.span());
+							prefixes.push((nodes.len(), text.clone(), span));
+							continue;
+						}
+						None if text != "'" => {
+							let reason = format!("expected a value directly after `{}`", text);
+							self.invalid_prefix(text, *span, reason);
+							continue;
+						}
+						None => {}
+					}
+				}
+				let object = nodes.last().map(|x| x.span());
+				let member = match terms.peek() {
+					Some(Term::Identifier { name, span: next }) => Some((name.clone(), *next)),
+					_ => None,
+				};
+				match (object, member) {
+					(Some(object), Some((name, member)))
+						if text == "." && adjacent(object, *span) && adjacent(*span, member) =>
+					{
+						terms.next();
+						if let Some(object) = nodes.pop() {
+							nodes.push(Node::Member {
+								span: object.span().to(member),
+								object: Box::new(object),
+								member: Identifier { name, span: member },
+							});
+						}
+						continue;
+					}
+					_ => {}
+				}
+			}
+			let node = self.term(term);
+			nodes.push(node);
+		}
+		for (index, prefix, span) in prefixes.into_iter().rev() {
+			if index >= nodes.len() {
+				let reason = format!("expected a value directly after `{}`", prefix);
+				self.invalid_prefix(&prefix, span, reason);
+				continue;
+			}
+			let operand = nodes.remove(index);
+			let span = match operand.span() {
+				end if end.end.offset > span.end.offset => span.to(end),
+				_ => span,
+			};
+			nodes.insert(index, prefixed_node(&prefix, operand, span));
+		}
+		nodes.extend(self.optional_block(block).map(Node::Block));
+		nodes
+	}
+
+	fn term(&mut self, term: Term) -> Node {
+		match term {
+			Term::Identifier { name, span } if name == "_" => Node::Placeholder(span),
+			Term::Identifier { name, span } => Node::Identifier(Identifier { name, span }),
+			Term::Literal { literal, span } => Node::Literal { literal, span },
+			Term::Symbol { text, span } if text == "!" => {
+				let reason = format!("nothing to evaluate at compile time here");
+				let error = self.misplaced(span, reason);
+				Node::Error { error, span }
+			}
+			Term::Symbol { text, span } => Node::Symbol { text, span },
+			Term::Subexpression { terms, span } => match self.node(terms, Block::None) {
+				Some(function @ Node::Identifier(_)) | Some(function @ Node::Member { .. }) => {
+					Node::Call {
+						function: Box::new(function),
+						arguments: Vec::new(),
+						span,
+					}
+				}
+				Some(node) => node,
+				None => Node::Tuple {
+					elements: Vec::new(),
+					span,
+				},
+			},
+			Term::Group { terms, span } => Node::Tuple {
+				elements: terms.into_iter().map(|x| self.term(x)).collect(),
+				span,
+			},
+			Term::Block { block, .. } => Node::Block(self.block(block)),
+			Term::Infix {
+				operator,
+				lhs,
+				rhs,
+				span,
+			} => Node::Infix {
+				operator,
+				lhs: Box::new(self.operand(lhs, span)),
+				rhs: Box::new(self.operand(rhs, span)),
+				span,
+			},
+			Term::Prefix {
+				operator,
+				operand,
+				span,
+			} => Node::Prefix {
+				operator,
+				operand: Box::new(self.operand(operand, span)),
+				span,
+			},
+			Term::Range {
+				start,
+				end,
+				inclusive,
+				span,
+			} => Node::Range {
+				start: Box::new(self.term(*start)),
+				end: Box::new(self.term(*end)),
+				inclusive,
+				span,
+			},
+			Term::Spread { operand, span } => Node::Spread {
+				operand: Box::new(self.term(*operand)),
+				span,
+			},
+			Term::Pipe { span, .. } => {
+				let terms = desugar_term(term);
+				self.operand(terms, span)
+			}
+			Term::Error { error, span } => Node::Error { error, span },
+		}
+	}
+
+	fn operand(&mut self, terms: Vec<Term>, span: Span) -> Node {
+		self.node(terms, Block::None).unwrap_or(Node::Tuple {
+			elements: Vec::new(),
+			span,
+		})
+	}
+
+	fn fundamental(
+		&mut self,
+		kind: FundamentalKind,
+		keyword: Span,
+		terms: Vec<Term>,
+		block: Block,
+	) -> Fundamental {
+		match kind {
+			FundamentalKind::Structure => {
+				return Fundamental::Structure(self.structure(keyword, terms, block))
+			}
+			FundamentalKind::Function => {
+				return Fundamental::Function(self.function(keyword, terms, block))
+			}
+			FundamentalKind::Enumeration => {
+				return Fundamental::Enumeration(self.enumeration(keyword, terms, block))
+			}
+			FundamentalKind::Trait => {
+				return Fundamental::Trait(self.trait_declaration(keyword, terms, block))
+			}
+			FundamentalKind::Module => {
+				return Fundamental::Module(self.module(keyword, terms, block))
+			}
+			FundamentalKind::Match => {
+				return Fundamental::Match(self.matching(keyword, terms, block))
+			}
+			_ => {}
+		}
+		let arguments = self.nodes(terms, block);
+		let span = match arguments.last() {
+			Some(last) => keyword.to(last.span()),
+			None => keyword,
+		};
+		let (arguments, block) = match arguments.last() {
+			Some(Node::Block(_)) => {
+				let mut arguments = arguments;
+				let block = match arguments.pop() {
+					Some(Node::Block(block)) => Some(block),
+					_ => None,
+				};
+				(arguments, block)
+			}
+			_ => (arguments, None),
+		};
+		Fundamental::Other {
+			kind,
+			arguments,
+			block,
+			span,
+		}
+	}
+
+	///
+	/// Record an error for a malformed fundamental
+	///
+	fn invalid(&mut self, declaration: &str, span: Span, reason: String) -> ParseError {
+		let error = InvalidDeclaration {
+			context: InputContext::default().at(span.start),
+			declaration: declaration.to_string(),
+			reason,
+		};
+		self.errors.push(error.clone());
+		error
+	}
+
+	fn misplaced(&mut self, span: Span, reason: String) -> ParseError {
+		let error = MisplacedCompileTime {
+			context: InputContext::default().at(span.start),
+			reason,
+		};
+		self.errors.push(error.clone());
+		error
+	}
+
+	fn invalid_prefix(&mut self, prefix: &str, span: Span, reason: String) {
+		self.errors.push(InvalidPrefix {
+			context: InputContext::default().at(span.start),
+			prefix: prefix.to_string(),
+			reason,
+		});
+	}
+
+	fn invalid_tag(&mut self, tag: &str, span: Span, reason: String) {
+		self.errors.push(InvalidTag {
+			context: InputContext::default().at(span.start),
+			tag: tag.to_string(),
+			reason,
+		});
+	}
+
+	fn structure(&mut self, keyword: Span, terms: Vec<Term>, block: Block) -> ast::Structure {
+		let (mut terms, mut lines, expressions, span) = declaration_body(keyword, terms, block);
+		let parameters = terms
+			.iter()
+			.enumerate()
+			.take_while(|(index, x)| is_name(Some(x)) && !is_symbol(terms.get(index + 1), ":"))
+			.count();
+		let members = terms.split_off(parameters);
+		lines.insert(
+			0,
+			Line {
+				terms: members,
+				block: Block::None,
+				comments: Comments::default(),
+				tags: Vec::new(),
+			},
+		);
+		lines.extend(expressions.into_iter().filter_map(line_of));
+
+		let mut structure = ast::Structure {
+			parameters: terms.into_iter().filter_map(identifier).collect(),
+			members: Vec::new(),
+			open: false,
+			span,
+		};
+		for line in self.tagged_lines("st", lines) {
+			let (members, open) = self.members("st", line.terms, line.block);
+			structure
+				.members
+				.extend(line_members(members, line.comments, line.tags));
+			structure.open |= open.is_some();
+		}
+		structure
+	}
+
+	///
+	/// `tr Parameters... {members}` with further members and `name :: value` definitions in the block
+	///
+	fn trait_declaration(&mut self, keyword: Span, terms: Vec<Term>, block: Block) -> ast::Trait {
+		let (terms, mut lines, expressions, span) = declaration_body(keyword, terms, block);
+		let mut definitions = Vec::new();
+		for expression in expressions {
+			match expression {
+				Expression::Binding {
+					binding: Binding {
+						kind: BindingKind::Define,
+						..
+					},
+					..
+				} => {
+					if let ExpressionKind::Binding(binding) = self.expression(expression).kind {
+						definitions.push(binding);
+					}
+				}
+				expression => lines.extend(line_of(expression)),
+			}
+		}
+
+		let mut declaration = ast::Trait {
+			parameters: self.type_parameters("tr", terms),
+			members: Vec::new(),
+			definitions,
+			span,
+		};
+		for line in self.tagged_lines("tr", lines) {
+			let (members, open) = self.members("tr", line.terms, line.block);
+			declaration
+				.members
+				.extend(line_members(members, line.comments, line.tags));
+			if let Some(span) = open {
+				self.invalid("tr", span, format!("only structures can be open with `_`"));
+			}
+		}
+		declaration
+	}
+
+	///
+	/// `en Type {variants}` with further variants `Name` or `Name = value` in the block
+	///
+	fn enumeration(&mut self, keyword: Span, terms: Vec<Term>, block: Block) -> ast::Enumeration {
+		let (terms, mut lines, expressions, span) = declaration_body(keyword, terms, block);
+		lines.extend(expressions.into_iter().filter_map(line_of));
+		let backing = match terms.is_empty() {
+			true => None,
+			false => Some(Box::new(self.annotation(terms))),
+		};
+		let mut variants = Vec::new();
+		for Line {
+			terms,
+			block,
+			mut comments,
+			tags,
+		} in self.tagged_lines("en", lines)
+		{
+			if let Block::Block { span, .. } = block {
+				self.invalid("en", span, format!("variants can't be followed by a block"));
+			}
+			let mut terms = terms.into_iter().peekable();
+			while let Some(term) = terms.next() {
+				let name = match term {
+					Term::Identifier { name, span } if name != "_" => Identifier { name, span },
+					term => {
+						let reason = format!("expected a variant `Name` or `Name = value`");
+						self.invalid("en", term.span(), reason);
+						continue;
+					}
+				};
+				let mut value = None;
+				if is_symbol(terms.peek(), "=") {
+					let equals = terms.next().map_or(name.span, |x| x.span());
+					value = Some(match terms.next() {
+						Some(term) => self.term(term),
+						None => {
+							let reason = format!("expected the value of `{}` after `=`", name.name);
+							let error = self.invalid("en", equals, reason);
+							Node::Error {
+								error,
+								span: equals,
+							}
+						}
+					});
+				}
+				variants.push(ast::Variant {
+					span: name.span.to(value.as_ref().map_or(name.span, |x| x.span())),
+					name,
+					value,
+					comments: std::mem::take(&mut comments),
+					tags: tags.clone(),
+				});
+			}
+		}
+		ast::Enumeration {
+			backing,
+			variants,
+			span,
+		}
+	}
+
+	///
+	/// Type parameters `Name` or `Name: Bound`
+	///
+	fn type_parameters(&mut self, declaration: &str, terms: Vec<Term>) -> Vec<ast::TypeParameter> {
+		let mut parameters = Vec::new();
+		let mut terms = terms.into_iter().peekable();
+		while let Some(term) = terms.next() {
+			let name = match term {
+				Term::Identifier { name, span } if name != "_" => Identifier { name, span },
+				term => {
+					let reason = format!("expected a type parameter");
+					self.invalid(declaration, term.span(), reason);
+					continue;
+				}
+			};
+			let mut span = name.span;
+			let mut bound = None;
+			if is_symbol(terms.peek(), ":") {
+				let colon = terms.next().map_or(span, |x| x.span());
+				let terms = prefixed(&mut terms);
+				span = span.to(terms_span(&terms).unwrap_or(colon));
+				let reason = format!("expected the bound of `{}` after `:`", name.name);
+				bound = Some(self.required_type(declaration, terms, colon, reason));
+			}
+			parameters.push(ast::TypeParameter { name, bound, span });
+		}
+		parameters
+	}
+
+	///
+	/// Members `name: Type = default` of a line and the span of a trailing `_`,
+	/// a fundamental in a member type and a block following the line belong to the last member
+	///
+	fn members(
+		&mut self,
+		declaration: &str,
+		mut terms: Vec<Term>,
+		mut block: Block,
+	) -> (Vec<ast::Member>, Option<Span>) {
+		let fundamental = terms.iter().position(|x| match x {
+			Term::Identifier { name, .. } => FundamentalKind::from_keyword(name).is_some(),
+			_ => false,
+		});
+		let open = match terms.len() {
+			_ if fundamental.is_some() => false,
+			0 => false,
+			length => {
+				is_placeholder(terms.last())
+					&& (length == 1 || !is_symbol(terms.get(length - 2), ":"))
+			}
+		};
+		let open = match open {
+			true => terms.pop().map(|x| x.span()),
+			false => None,
+		};
+		let starts = (0..fundamental.unwrap_or(terms.len()))
+			.filter(|x| is_name(terms.get(*x)) && is_symbol(terms.get(x + 1), ":"))
+			.collect::<Vec<_>>();
+		let mut members = Vec::new();
+		for start in starts.into_iter().rev() {
+			let mut segment = terms.split_off(start);
+			let rest = segment.split_off(2);
+			let block = std::mem::replace(&mut block, Block::None);
+			if let [Term::Identifier { name, span }, colon] = segment.as_slice() {
+				let name = Identifier {
+					name: name.clone(),
+					span: *span,
+				};
+				members.push(self.member(declaration, name, colon.span(), rest, block));
+			}
+		}
+		if let Some(span) = terms_span(&terms) {
+			self.invalid(declaration, span, format!("expected a member `name: Type`"));
+		}
+		members.reverse();
+		(members, open)
+	}
+
+	fn member(
+		&mut self,
+		declaration: &str,
+		name: Identifier,
+		colon: Span,
+		mut terms: Vec<Term>,
+		block: Block,
+	) -> ast::Member {
+		let default = match terms.iter().position(|x| is_symbol(Some(x), "=")) {
+			Some(equals) => {
+				let default = terms.split_off(equals + 1);
+				let equals = terms.pop().map_or(colon, |x| x.span());
+				let node = self.node(default, block);
+				Some(node.unwrap_or_else(|| {
+					let reason = format!("expected a default value for `{}`", name.name);
+					let error = self.invalid(declaration, equals, reason);
+					Node::Error {
+						error,
+						span: equals,
+					}
+				}))
+			}
+			None => {
+				terms.extend(lines_of(block).into_iter().flat_map(|x| x.terms));
+				None
+			}
+		};
+		let annotation = match self.node(terms, Block::None) {
+			Some(node) => node_type(node),
+			None => {
+				let reason = format!("expected the type of `{}`", name.name);
+				let error = self.invalid(declaration, colon, reason);
+				Type::Error { error, span: colon }
+			}
+		};
+		let end = default.as_ref().map_or(annotation.span(), |x| x.span());
+		ast::Member {
+			span: name.span.to(end),
+			name,
+			annotation,
+			default,
+			comments: Comments::default(),
+			tags: Vec::new(),
+		}
+	}
+
+	fn function(&mut self, keyword: Span, mut terms: Vec<Term>, block: Block) -> ast::Function {
+		let end = match &block {
+			Block::Block { span, .. } => Some(*span),
+			Block::None => terms_span(&terms),
+		};
+		let body = match terms.iter().position(|x| is_symbol(Some(x), "=>")) {
+			Some(arrow) => {
+				let body = terms.split_off(arrow + 1);
+				let arrow = terms.pop().map_or(keyword, |x| x.span());
+				let node = self.node(body, block);
+				Some(node.unwrap_or_else(|| {
+					let reason = format!("expected the function body after `=>`");
+					let error = self.invalid("fn", arrow, reason);
+					Node::Error { error, span: arrow }
+				}))
+			}
+			None => match (terms.last(), block) {
+				(Some(Term::Block { .. }), block) => {
+					if let Block::Block { span, .. } = block {
+						let reason = format!("the function body is already given in `{{}}`");
+						self.invalid("fn", span, reason);
+					}
+					terms.pop().map(|x| self.term(x))
+				}
+				(_, block) => self.optional_block(block).map(Node::Block),
+			},
+		};
+		let result = match terms.iter().position(|x| is_symbol(Some(x), "->")) {
+			Some(arrow) => {
+				let result = terms.split_off(arrow + 1);
+				let arrow = terms.pop().map_or(keyword, |x| x.span());
+				let reason = format!("expected the return type after `->`");
+				Some(self.required_type("fn", result, arrow, reason))
+			}
+			None => None,
+		};
+		ast::Function {
+			parameters: self.parameters(terms),
+			result: result.map(Box::new),
+			body: body.map(Box::new),
+			compile_time: false,
+			span: keyword.to(end.unwrap_or(keyword)),
+		}
+	}
+
+	///
+	/// Parameters `pattern: Type` with optional type and `...`, preceded by `&` or `$` to take a reference or copy
+	///
+	fn parameters(&mut self, terms: Vec<Term>) -> Vec<ast::Parameter> {
+		let mut parameters = Vec::new();
+		let mut terms = terms.into_iter().peekable();
+		while terms.peek().is_some() {
+			let mut pattern = prefixed(&mut terms);
+			let start = terms_span(&pattern).unwrap_or_default();
+			let passing = match pattern.first() {
+				Some(Term::Symbol { text, .. }) if text == "&" => Passing::Reference,
+				Some(Term::Symbol { text, .. }) if text == "$" => Passing::Copy,
+				_ => Passing::Move,
+			};
+			if passing != Passing::Move {
+				pattern.remove(0);
+			}
+			match pattern.as_slice() {
+				[] => {
+					self.invalid("fn", start, format!("expected a parameter"));
+					continue;
+				}
+				[Term::Symbol { text, span }] if text != "%" => {
+					let reason = format!("expected a parameter, found `{}`", text);
+					self.invalid("fn", *span, reason);
+					continue;
+				}
+				_ => {}
+			}
+			let mut end = start;
+			let mut annotation = None;
+			if is_symbol(terms.peek(), ":") {
+				let colon = terms.next().map_or(start, |x| x.span());
+				let terms = prefixed(&mut terms);
+				end = terms_span(&terms).unwrap_or(colon);
+				let reason = format!("expected the parameter type after `:`");
+				annotation = Some(self.required_type("fn", terms, colon, reason));
+			}
+			if let [Term::Symbol { span, .. }] = pattern.as_slice() {
+				if annotation.is_none() {
+					annotation = Some(Type::Implementor(*span));
+					pattern = vec![Term::Identifier {
+						name: format!("_"),
+						span: *span,
+					}];
+				}
+			}
+			let variadic = is_symbol(terms.peek(), "...");
+			if variadic {
+				end = terms.next().map_or(end, |x| x.span());
+			}
+			parameters.push(ast::Parameter {
+				pattern: self.pattern(pattern),
+				annotation,
+				passing,
+				variadic,
+				span: start.to(end),
+			});
+		}
+		parameters
+	}
+
+	///
+	/// `md "name" "version"`
+	///
+	fn module(&mut self, keyword: Span, terms: Vec<Term>, block: Block) -> ast::Module {
+		let span = keyword.to(terms_span(&terms).unwrap_or(keyword));
+		if let Block::Block { span, .. } = block {
+			self.invalid("md", span, format!("modules can't be followed by a block"));
+		}
+		let mut strings = Vec::new();
+		for term in terms {
+			match term {
+				Term::Literal {
+					literal: Literal::String(string),
+					..
+				} if strings.len() < 2 => strings.push(string),
+				term => {
+					let reason = format!("expected the module name and version as strings");
+					self.invalid("md", term.span(), reason);
+				}
+			}
+		}
+		let mut strings = strings.into_iter();
+		let name = strings.next().unwrap_or_else(|| {
+			let reason = format!("expected the module name as a string");
+			self.invalid("md", keyword, reason);
+			String::new()
+		});
+		ast::Module {
+			name,
+			version: strings.next(),
+			span,
+		}
+	}
+
+	///
+	/// `mt value` with the arms `pattern` followed by their body in the block
+	///
+	fn matching(&mut self, keyword: Span, terms: Vec<Term>, block: Block) -> ast::Match {
+		let (expressions, end) = match block {
+			Block::Block { expressions, span } => (expressions, Some(span)),
+			Block::None => (Vec::new(), terms_span(&terms)),
+		};
+		let span = keyword.to(end.unwrap_or(keyword));
+		let value = self.node(terms, Block::None).unwrap_or_else(|| {
+			let reason = format!("expected the value to match");
+			let error = self.invalid("mt", keyword, reason);
+			Node::Error {
+				error,
+				span: keyword,
+			}
+		});
+		if expressions.is_empty() {
+			self.invalid("mt", keyword, format!("expected arms in an indented block"));
+		}
+		let mut arms = Vec::new();
+		for expression in expressions {
+			let (terms, block, span) = match expression {
+				Expression::Expression {
+					terms, block, span, ..
+				} => (terms, block, span),
+				Expression::Binding { span, .. } => {
+					let reason = format!("expected a pattern followed by the arm body");
+					self.invalid("mt", span, reason);
+					continue;
+				}
+				Expression::Error { .. } => continue,
+			};
+			if let Block::None = block {
+				self.invalid(
+					"mt",
+					span,
+					format!("expected the arm body in an indented block"),
+				);
+			}
+			let pattern = self.arm_pattern(terms, span);
+			let body = self.block(block);
+			arms.push(ast::Arm {
+				span: span.to(if body.expressions.is_empty() {
+					span
+				} else {
+					body.span
+				}),
+				pattern,
+				body,
+			});
+		}
+		ast::Match {
+			value: Box::new(value),
+			arms,
+			span,
+		}
+	}
+
+	///
+	/// Calls of functions are guards, names, fundamentals and operators on them are types
+	///
+	fn arm_pattern(&mut self, terms: Vec<Term>, span: Span) -> ast::ArmPattern {
+		match self.operand(terms, span) {
+			Node::Placeholder(span) => ast::ArmPattern::Wildcard(span),
+			Node::Call {
+				function,
+				arguments,
+				span,
+			} if path(&function).is_some() => ast::ArmPattern::Guard {
+				function: *function,
+				arguments,
+				span,
+			},
+			node @ Node::Identifier(_)
+			| node @ Node::Member { .. }
+			| node @ Node::Fundamental(_)
+			| node @ Node::Infix { .. }
+			| node @ Node::Error { .. } => ast::ArmPattern::Type(node_type(node)),
+			node => ast::ArmPattern::Value(node),
+		}
+	}
+
+	fn pattern(&mut self, mut terms: Vec<Term>) -> Pattern {
+		if terms.len() == 1 {
+			match terms.pop() {
+				Some(Term::Identifier { name, span }) if name == "_" => {
+					return Pattern::Placeholder(span)
+				}
+				Some(Term::Identifier { name, span }) => {
+					return Pattern::Identifier(Identifier { name, span })
+				}
+				Some(Term::Literal { literal, span }) => return Pattern::Literal { literal, span },
+				Some(Term::Group { terms, span }) => {
+					return Pattern::Tuple {
+						elements: terms.into_iter().map(|x| self.pattern(vec![x])).collect(),
+						span,
+					}
+				}
+				Some(Term::Error { error, span }) => return Pattern::Error { error, span },
+				Some(term) => terms.push(term),
+				None => {}
+			}
+		}
+		let span = terms_span(&terms).unwrap_or_default();
+		Pattern::Value(self.operand(terms, span))
+	}
+
+	fn annotation(&mut self, terms: Vec<Term>) -> Type {
+		let span = terms_span(&terms).unwrap_or_default();
+		let node = self.operand(terms, span);
+		node_type(node)
+	}
+
+	///
+	/// Type of non-empty terms, recording an error located after `after` otherwise
+	///
+	fn required_type(
+		&mut self,
+		declaration: &str,
+		terms: Vec<Term>,
+		after: Span,
+		reason: String,
+	) -> Type {
+		match terms.is_empty() {
+			true => {
+				let error = self.invalid(declaration, after, reason);
+				Type::Error { error, span: after }
+			}
+			false => self.annotation(terms),
+		}
+	}
+}
+
+///
+/// Names joined by member access, e.g. `math.Vector`
+///
+fn path(node: &Node) -> Option<Vec<Identifier>> {
+	match node {
+		Node::Identifier(identifier) => Some(vec![identifier.clone()]),
+		Node::Member { object, member, .. } => {
+			let mut path = path(object)?;
+			path.push(member.clone());
+			Some(path)
+		}
+		_ => None,
+	}
+}
+
+///
+/// Interpret a node in type position
+///
+pub fn node_type(node: Node) -> Type {
+	if let Some(path) = path(&node) {
+		return Type::Named {
+			path,
+			arguments: Vec::new(),
+			span: node.span(),
+		};
+	}
+	match node {
+		Node::Placeholder(span) => Type::Placeholder(span),
+		Node::Symbol { text, span } if text == "%" => Type::Implementor(span),
+		Node::TypeLiteral { annotation, span } => Type::Literal { annotation, span },
+		Node::Reference { operand, span } => Type::Reference {
+			annotation: Box::new(node_type(*operand)),
+			span,
+		},
+		Node::Infix {
+			operator,
+			lhs,
+			rhs,
+			span,
+		} if operator == "|" || operator == "+" => {
+			let mut variants = Vec::new();
+			for side in vec![*lhs, *rhs] {
+				match node_type(side) {
+					Type::Sum {
+						variants: inner, ..
+					} if operator == "|" => variants.extend(inner),
+					Type::Union {
+						variants: inner, ..
+					} if operator == "+" => variants.extend(inner),
+					side => variants.push(side),
+				}
+			}
+			match operator.as_str() {
+				"|" => Type::Sum { variants, span },
+				_ => Type::Union { variants, span },
+			}
+		}
+		Node::Fundamental(fundamental) => Type::Fundamental(Box::new(fundamental)),
+		Node::Error { error, span } => Type::Error { error, span },
+		Node::Call {
+			function,
+			arguments,
+			span,
+		} => match path(&function) {
+			Some(path) => Type::Named {
+				path,
+				arguments: arguments.into_iter().map(node_type).collect(),
+				span,
+			},
+			None => Type::Value(Node::Call {
+				function,
+				arguments,
+				span,
+			}),
+		},
+		node => Type::Value(node),
+	}
+}
+
+///
+/// Strip a leading `!` marking the terms for compile-time evaluation,
+/// a `!` before a declaration other than `mt` is left to `nodes`
+///
+fn compile_time(terms: &mut Vec<Term>) -> bool {
+	let marks = match (terms.get(0), terms.get(1)) {
+		(Some(Term::Symbol { text, span }), Some(next))
+			if text == "!" && span.end.offset == next.span().start.offset =>
+		{
+			match next {
+				Term::Identifier { name, .. } => {
+					name != "im"
+						&& FundamentalKind::from_keyword(name)
+							.map_or(true, |x| x == FundamentalKind::Match)
+				}
+				_ => true,
+			}
+		}
+		_ => false,
+	};
+	if marks {
+		terms.remove(0);
+	}
+	marks
+}
+
+///
+/// Lines of a block continuing the terms before it, or the block if the terms contain a fundamental
+/// taking the block or the block contains statements
+///
+fn is_continuation(terms: &[Term], block: &Block) -> bool {
+	let fundamental = terms.iter().any(|x| match x {
+		Term::Identifier { name, .. } => FundamentalKind::from_keyword(name).is_some(),
+		_ => false,
+	});
+	match block {
+		Block::Block { expressions, .. } if !fundamental => expressions.iter().all(|x| match x {
+			Expression::Expression { terms, .. } => {
+				!terms.is_empty()
+					&& !is_keyword(terms.first(), "im")
+					&& !is_symbol(terms.first(), "@")
+					&& !is_symbol(terms.first(), "!")
+			}
+			_ => false,
+		}),
+		_ => false,
+	}
+}
+
+fn continuation(terms: &[Term], block: Block) -> Result<Vec<(Vec<Term>, Block)>, Block> {
+	match block {
+		Block::Block { expressions, .. } if is_continuation(terms, &block) => Result::Ok(
+			expressions
+				.into_iter()
+				.filter_map(|x| match x {
+					Expression::Expression { terms, block, .. } => Some((terms, block)),
+					_ => None,
+				})
+				.collect(),
+		),
+		block => Result::Err(block),
+	}
+}
+
+///
+/// Take the comments of continuation lines, they are kept with the expression the lines continue
+///
+fn continued_comments(terms: &[Term], block: &mut Block) -> Vec<Comment> {
+	if !is_continuation(terms, block) {
+		return Vec::new();
+	}
+	let mut continued = Vec::new();
+	if let Block::Block { expressions, .. } = block {
+		for expression in expressions {
+			if let Expression::Expression { comments, .. } = expression {
+				let comments = std::mem::take(comments);
+				continued.extend(comments.leading);
+				continued.extend(comments.trailing);
+				continued.extend(comments.dangling);
+			}
+		}
+	}
+	continued
+}
+
+fn is_prefix(symbol: &str) -> bool {
+	["&", "$", "'"].contains(&symbol)
+}
+
+///
+/// `$value`, `&value` or `'Type` applied to the lowered operand
+///
+fn prefixed_node(prefix: &str, operand: Node, span: Span) -> Node {
+	let operand = Box::new(operand);
+	match prefix {
+		"$" => Node::Copy { operand, span },
+		"&" => Node::Reference { operand, span },
+		_ => Node::TypeLiteral {
+			annotation: Box::new(node_type(*operand)),
+			span,
+		},
+	}
+}
+
+fn is_keyword(term: Option<&Term>, keyword: &str) -> bool {
+	match term {
+		Some(Term::Identifier { name, .. }) => name == keyword,
+		_ => false,
+	}
+}
+
+fn is_symbol(term: Option<&Term>, symbol: &str) -> bool {
+	match term {
+		Some(Term::Symbol { text, .. }) => text == symbol,
+		_ => false,
+	}
+}
+
+fn is_placeholder(term: Option<&Term>) -> bool {
+	match term {
+		Some(Term::Identifier { name, .. }) => name == "_",
+		_ => false,
+	}
+}
+
+///
+/// Identifier other than the placeholder
+///
+fn is_name(term: Option<&Term>) -> bool {
+	match term {
+		Some(Term::Identifier { name, .. }) => name != "_",
+		_ => false,
+	}
+}
+
+///
+/// Next term with the `&`, `$` and `'` symbols preceding it
+///
+fn prefixed(terms: &mut Peekable<impl Iterator<Item = Term>>) -> Vec<Term> {
+	let mut prefixed = Vec::new();
+	while let Some(term) = terms.next() {
+		let prefix = match &term {
+			Term::Symbol { text, .. } => is_prefix(text),
+			_ => false,
+		};
+		prefixed.push(term);
+		if !prefix {
+			break;
+		}
+	}
+	prefixed
+}
+
+fn identifier(term: Term) -> Option<Identifier> {
+	match term {
+		Term::Identifier { name, span } => Some(Identifier { name, span }),
+		_ => None,
+	}
+}
+
+///
+/// Member or variant line of a declaration
+///
+struct Line {
+	terms: Vec<Term>,
+	block: Block,
+	comments: Comments,
+	tags: Vec<ast::Tag>,
+}
+
+///
+/// Members declared on a line, the comments of the line belong to its first member
+///
+fn line_members(
+	members: Vec<ast::Member>,
+	mut comments: Comments,
+	tags: Vec<ast::Tag>,
+) -> impl Iterator<Item = ast::Member> {
+	members.into_iter().map(move |x| ast::Member {
+		comments: std::mem::take(&mut comments),
+		tags: tags.clone(),
+		..x
+	})
+}
+
+///
+/// Split a declaration into its leading terms, the lines of a trailing `{}` block
+/// and the expressions of its indented block, spanning from the keyword to the end of either
+///
+fn declaration_body(
+	keyword: Span,
+	mut terms: Vec<Term>,
+	block: Block,
+) -> (Vec<Term>, Vec<Line>, Vec<Expression>, Span) {
+	let end = match &block {
+		Block::Block { span, .. } => Some(*span),
+		Block::None => terms_span(&terms),
+	};
+	let mut lines = Vec::new();
+	if let Some(Term::Block { .. }) = terms.last() {
+		if let Some(Term::Block { block, .. }) = terms.pop() {
+			lines.extend(lines_of(block));
+		}
+	}
+	let expressions = match block {
+		Block::Block { expressions, .. } => expressions,
+		Block::None => Vec::new(),
+	};
+	(
+		terms,
+		lines,
+		expressions,
+		keyword.to(end.unwrap_or(keyword)),
+	)
+}
+
+///
+/// Terms of each expression in a block as written, with binding operators and `=` put back in place
+///
+fn lines_of(block: Block) -> Vec<Line> {
+	match block {
+		Block::Block { expressions, .. } => expressions.into_iter().filter_map(line_of).collect(),
+		Block::None => Vec::new(),
+	}
+}
+
+fn line_of(expression: Expression) -> Option<Line> {
+	match expression {
+		Expression::Expression {
+			terms,
+			comments,
+			block,
+			..
+		} => Some(Line {
+			terms,
+			block,
+			comments,
+			tags: Vec::new(),
+		}),
+		Expression::Binding {
+			binding,
+			comments,
+			block,
+			..
+		} => {
+			let mut terms = binding.lhs;
+			terms.push(Term::Symbol {
+				text: binding.kind.operator().to_string(),
+				span: binding.operator,
+			});
+			if let Some(annotation) = binding.annotation {
+				let end = terms_span(&annotation).map_or(binding.operator.end, |x| x.end);
+				terms.extend(annotation);
+				terms.push(Term::Symbol {
+					text: format!("="),
+					span: Span::new(end, end),
+				});
+			}
+			terms.extend(binding.rhs);
+			Some(Line {
+				terms,
+				block,
+				comments,
+				tags: Vec::new(),
+			})
+		}
+		Expression::Error { .. } => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::{parse_source, ParseOptions};
+	use super::*;
+
+	fn lower_source(source: &str) -> ast::Block {
+		let info = parse_source(source.to_string(), &ParseOptions::default());
+		assert!(info.errors.is_empty(), "{:?}", info.errors);
+		info.block
+	}
+
+	///
+	/// Lowered expressions written compactly, one per line: calls in `()`, fundamentals with their parts in `{}`
+	///
+	fn lowered(source: &str) -> String {
+		let block = lower_source(source);
+		let expressions = block.expressions.iter().map(show_expression);
+		expressions.collect::<Vec<_>>().join("\n")
+	}
+
+	fn join<T>(items: &[T], show: impl Fn(&T) -> String, separator: &str) -> String {
+		items.iter().map(show).collect::<Vec<_>>().join(separator)
+	}
+
+	fn show_expression(expression: &ast::Expression) -> String {
+		let marker = if expression.compile_time { "!" } else { "" };
+		let kind = match &expression.kind {
+			ExpressionKind::Binding(binding) => {
+				let mut text = format!(
+					"{} {}",
+					show_pattern(&binding.target),
+					binding.kind.operator()
+				);
+				if let Some(annotation) = &binding.annotation {
+					text += &format!(" {}", show_type(annotation));
+					if binding.value.is_some() {
+						text += " =";
+					}
+				}
+				if binding.compile_time {
+					text += " !";
+				}
+				if let Some(value) = &binding.value {
+					text += &format!(" {}", show(value));
+				}
+				text
+			}
+			ExpressionKind::Implementation(implementation) => format!(
+				"{} :+ {} {{{}}}",
+				show_type(&implementation.trait_type),
+				show_type(&implementation.target),
+				join(&implementation.block.expressions, show_expression, ", ")
+			),
+			ExpressionKind::Import(import) => format!("im {}", show(&import.module)),
+			ExpressionKind::Node(node) => show(node),
+			ExpressionKind::Error(_) => format!("<error>"),
+		};
+		format!("{}{}", marker, kind)
+	}
+
+	fn show_literal(literal: &Literal) -> String {
+		match literal {
+			Literal::Integer(value) => value.to_string(),
+			Literal::Float(value) => format!("{:?}", value),
+			Literal::String(value) => format!("{:?}", value),
+		}
+	}
+
+	fn show(node: &Node) -> String {
+		match node {
+			Node::Identifier(identifier) => identifier.name.clone(),
+			Node::Placeholder(_) => format!("_"),
+			Node::Literal { literal, .. } => show_literal(literal),
+			Node::Member { object, member, .. } => format!("{}.{}", show(object), member.name),
+			Node::Call {
+				function,
+				arguments,
+				..
+			} => format!("({} {})", show(function), join(arguments, show, " ")),
+			Node::Infix {
+				operator, lhs, rhs, ..
+			} => format!("({} {} {})", show(lhs), operator, show(rhs)),
+			Node::Prefix {
+				operator, operand, ..
+			} => format!("({}{})", operator, show(operand)),
+			Node::Range {
+				start,
+				end,
+				inclusive,
+				..
+			} => {
+				let operator = if *inclusive { "..=" } else { ".." };
+				format!("{}{}{}", show(start), operator, show(end))
+			}
+			Node::Spread { operand, .. } => format!("..{}", show(operand)),
+			Node::Tuple { elements, .. } => format!("({})", join(elements, show, " ")),
+			Node::Block(block) => {
+				format!("{{{}}}", join(&block.expressions, show_expression, ", "))
+			}
+			Node::Fundamental(fundamental) => show_fundamental(fundamental),
+			Node::TypeLiteral { annotation, .. } => format!("'{}", show_type(annotation)),
+			Node::Copy { operand, .. } => format!("${}", show(operand)),
+			Node::Reference { operand, .. } => format!("&{}", show(operand)),
+			Node::Symbol { text, .. } => text.clone(),
+			Node::Error { .. } => format!("<error>"),
+		}
+	}
+
+	fn show_member(member: &ast::Member) -> String {
+		let mut text = format!("{}: {}", member.name.name, show_type(&member.annotation));
+		if let Some(default) = &member.default {
+			text += &format!(" = {}", show(default));
+		}
+		text
+	}
+
+	fn show_parameter(parameter: &ast::Parameter) -> String {
+		let passing = match parameter.passing {
+			Passing::Move => "",
+			Passing::Copy => "$",
+			Passing::Reference => "&",
+		};
+		let mut text = format!("{}{}", passing, show_pattern(&parameter.pattern));
+		if let Some(annotation) = &parameter.annotation {
+			text += &format!(": {}", show_type(annotation));
+		}
+		if parameter.variadic {
+			text += "...";
+		}
+		text
+	}
+
+	fn show_arm(arm: &ast::Arm) -> String {
+		let pattern = match &arm.pattern {
+			ast::ArmPattern::Wildcard(_) => format!("_"),
+			ast::ArmPattern::Value(value) => show(value),
+			ast::ArmPattern::Guard {
+				function,
+				arguments,
+				..
+			} => format!("?({} {})", show(function), join(arguments, show, " ")),
+			ast::ArmPattern::Type(annotation) => format!(":{}", show_type(annotation)),
+		};
+		let body = join(&arm.body.expressions, show_expression, ", ");
+		format!("{} => {}", pattern, body)
+	}
+
+	fn show_fundamental(fundamental: &Fundamental) -> String {
+		let parameters = |parameters: &[Identifier]| {
+			parameters
+				.iter()
+				.map(|x| format!("{} ", x.name))
+				.collect::<String>()
+		};
+		match fundamental {
+			Fundamental::Structure(structure) => {
+				let mut members = structure
+					.members
+					.iter()
+					.map(show_member)
+					.collect::<Vec<_>>();
+				if structure.open {
+					members.push(format!("_"));
+				}
+				let parameters = parameters(&structure.parameters);
+				format!("st {}{{{}}}", parameters, members.join(", "))
+			}
+			Fundamental::Function(function) => {
+				let mut text = format!("fn({})", join(&function.parameters, show_parameter, ", "));
+				if function.compile_time {
+					text.insert(0, '!');
+				}
+				if let Some(result) = &function.result {
+					text += &format!(" -> {}", show_type(result));
+				}
+				if let Some(body) = &function.body {
+					text += &format!(" => {}", show(body));
+				}
+				text
+			}
+			Fundamental::Trait(declaration) => {
+				let parameters = declaration.parameters.iter().map(|x| match &x.bound {
+					Some(bound) => format!("{}: {} ", x.name.name, show_type(bound)),
+					None => format!("{} ", x.name.name),
+				});
+				let definitions = declaration.definitions.iter().map(|x| {
+					let value = x.value.as_ref().map_or(String::new(), show);
+					format!(
+						"{} {} {}",
+						show_pattern(&x.target),
+						x.kind.operator(),
+						value
+					)
+				});
+				format!(
+					"tr {}{{{}}} {{{}}}",
+					parameters.collect::<String>(),
+					join(&declaration.members, show_member, ", "),
+					definitions.collect::<Vec<_>>().join(", ")
+				)
+			}
+			Fundamental::Enumeration(enumeration) => {
+				let backing = enumeration
+					.backing
+					.as_ref()
+					.map_or(String::new(), |x| format!("{} ", show_type(x)));
+				let variants = enumeration.variants.iter().map(|x| match &x.value {
+					Some(value) => format!("{} = {}", x.name.name, show(value)),
+					None => x.name.name.clone(),
+				});
+				format!(
+					"en {}{{{}}}",
+					backing,
+					variants.collect::<Vec<_>>().join(", ")
+				)
+			}
+			Fundamental::Module(module) => match &module.version {
+				Some(version) => format!("md {:?} {:?}", module.name, version),
+				None => format!("md {:?}", module.name),
+			},
+			Fundamental::Match(matching) => format!(
+				"mt {} {{{}}}",
+				show(&matching.value),
+				join(&matching.arms, show_arm, "; ")
+			),
+			Fundamental::Other {
+				kind,
+				arguments,
+				block,
+				..
+			} => {
+				let block = block.as_ref().map_or(String::new(), |x| {
+					format!(" {{{}}}", join(&x.expressions, show_expression, ", "))
+				});
+				format!("{} {}{}", kind.keyword(), join(arguments, show, " "), block)
+			}
+		}
+	}
+
+	fn show_pattern(pattern: &Pattern) -> String {
+		match pattern {
+			Pattern::Identifier(identifier) => identifier.name.clone(),
+			Pattern::Placeholder(_) => format!("_"),
+			Pattern::Literal { literal, .. } => show_literal(literal),
+			Pattern::Tuple { elements, .. } => format!("({})", join(elements, show_pattern, " ")),
+			Pattern::Typed {
+				pattern,
+				annotation,
+				..
+			} => format!("{}: {}", show_pattern(pattern), show_type(annotation)),
+			Pattern::Value(value) => show(value),
+			Pattern::Error { .. } => format!("<error>"),
+		}
+	}
+
+	fn show_type(annotation: &Type) -> String {
+		match annotation {
+			Type::Named {
+				path, arguments, ..
+			} => {
+				let path = join(path, |x| x.name.clone(), ".");
+				match arguments.is_empty() {
+					true => path,
+					false => format!("({} {})", path, join(arguments, show_type, " ")),
+				}
+			}
+			Type::Placeholder(_) => format!("_"),
+			Type::Implementor(_) => format!("%"),
+			Type::Literal { annotation, .. } => format!("'{}", show_type(annotation)),
+			Type::Reference { annotation, .. } => format!("&{}", show_type(annotation)),
+			Type::Sum { variants, .. } => format!("({})", join(variants, show_type, " | ")),
+			Type::Union { variants, .. } => format!("({})", join(variants, show_type, " + ")),
+			Type::Fundamental(fundamental) => show_fundamental(fundamental),
+			Type::Value(value) => show(value),
+			Type::Error { .. } => format!("<error>"),
+		}
+	}
+
+	#[test]
+	fn continuation_lines() {
+		let block = lower_source(
+			"filtered :\n\t# keep the even numbers\n\tfilter fn e l => Boolean ' mod e 2\n\t5 6 7 8 9 # from five\n",
+		);
+		let expression = &block.expressions[0];
+		let comments = expression
+			.comments
+			.dangling
+			.iter()
+			.map(|x| x.text.trim())
+			.collect::<Vec<_>>();
+		assert_eq!(comments, vec!["keep the even numbers", "from five"]);
+		match &expression.kind {
+			ExpressionKind::Binding(ast::Binding {
+				value: Some(Node::Call {
+					function,
+					arguments,
+					..
+				}),
+				..
+			}) => {
+				match function.as_ref() {
+					Node::Identifier(Identifier { name, .. }) => assert_eq!(name, "filter"),
+					function => panic!("called {:?}", function),
+				}
+				assert_eq!(arguments.len(), 6);
+				assert!(arguments[1..].iter().all(|x| match x {
+					Node::Literal { .. } => true,
+					_ => false,
+				}));
+			}
+			kind => panic!("lowered to {:?}", kind),
+		}
+	}
+
+	fn fundamental(block: &ast::Block, index: usize) -> &Fundamental {
+		match &block.expressions[index].kind {
+			ExpressionKind::Binding(ast::Binding {
+				value: Some(Node::Fundamental(fundamental)),
+				..
+			}) => fundamental,
+			kind => panic!("lowered to {:?}", kind),
+		}
+	}
+
+	fn tag_names(tags: &[ast::Tag]) -> Vec<&str> {
+		tags.iter().map(|x| x.name.name.as_str()).collect()
+	}
+
+	#[test]
+	fn member_tags() {
+		let block = lower_source(
+			"@alias cte [assume compile-time-evaluated]\nPoint :: st\n\t@define packed\n\tx: S32 @cte\n\ty: S32\nColor :: en\n\tRed @define red\n\tGreen\n",
+		);
+		match fundamental(&block, 0) {
+			Fundamental::Structure(structure) => {
+				let tags = structure
+					.members
+					.iter()
+					.map(|x| tag_names(&x.tags))
+					.collect::<Vec<_>>();
+				assert_eq!(tags, vec![vec!["define", "assume"], vec![]]);
+				assert_eq!(structure.members[0].tags[1].arguments.len(), 1);
+			}
+			fundamental => panic!("lowered to {:?}", fundamental),
+		}
+		match fundamental(&block, 1) {
+			Fundamental::Enumeration(enumeration) => {
+				let tags = enumeration
+					.variants
+					.iter()
+					.map(|x| tag_names(&x.tags))
+					.collect::<Vec<_>>();
+				assert_eq!(tags, vec![vec!["define"], vec![]]);
+			}
+			fundamental => panic!("lowered to {:?}", fundamental),
+		}
+	}
+
+	#[test]
+	fn scoped_aliases() {
+		let block = lower_source(
+			"f :: fn =>\n\t@alias local [define inner]\n\t@local\n\tprint 1\n@local\nprint 2\n",
+		);
+		let tags = &block.expressions[1].tags;
+		assert_eq!(tag_names(tags), vec!["local"]);
+		assert!(tags[0].arguments.is_empty());
+	}
+
+	#[test]
+	fn member_comments() {
+		let block = lower_source(
+			"Point :: st\n\t# horizontal\n\tx: S32 # pixels\n\t# vertical\n\t@define packed\n\ty: S32\nColor :: en\n\t# primary\n\tRed Green\n",
+		);
+		let text = |comments: &Comments| {
+			comments
+				.leading
+				.iter()
+				.chain(&comments.trailing)
+				.map(|x| x.text.trim().to_string())
+				.collect::<Vec<_>>()
+		};
+		match fundamental(&block, 0) {
+			Fundamental::Structure(structure) => {
+				let members = &structure.members;
+				assert_eq!(text(&members[0].comments), vec!["horizontal", "pixels"]);
+				assert_eq!(text(&members[1].comments), vec!["vertical"]);
+			}
+			fundamental => panic!("lowered to {:?}", fundamental),
+		}
+		match fundamental(&block, 1) {
+			Fundamental::Enumeration(enumeration) => {
+				let variants = &enumeration.variants;
+				assert_eq!(text(&variants[0].comments), vec!["primary"]);
+				assert!(text(&variants[1].comments).is_empty());
+			}
+			fundamental => panic!("lowered to {:?}", fundamental),
+		}
+	}
+
+	#[test]
+	fn spaced_ranges() {
+		for source in &["a := 5 .. 9", "a := 5..= 9", "a := x ..=y"] {
+			let info = parse_source(source.to_string(), &ParseOptions::default());
+			match info.errors.as_slice() {
+				[UnexpectedToken { expected, .. }] => assert!(expected.contains("without spaces")),
+				errors => panic!("{} parsed with {:?}", source, errors),
+			}
+		}
+		lower_source("a := 5..9\nb := f ..xs\n");
+	}
+
+	#[test]
+	fn structures() {
+		assert_eq!(
+			lowered("Struct :: st TypeA TypeB\n\ti: U32\n\tb: TypeA\n\tc: Option TypeB = 5\n"),
+			"Struct :: st TypeA TypeB {i: U32, b: TypeA, c: (Option TypeB) = 5}"
+		);
+		assert_eq!(
+			lowered("Point :: st {x: F32 y: F32 = 0.5}\n"),
+			"Point :: st {x: F32, y: F32 = 0.5}"
+		);
+		assert_eq!(
+			lowered("Open :: st a: S32 _\nEmpty :: st _\n"),
+			"Open :: st {a: S32, _}\nEmpty :: st {_}"
+		);
+		assert_eq!(
+			lowered("struct :: st A: [st B C: [st {x: i32}] {x: B}] z: i32\n\ty: A\n"),
+			"struct :: st {A: st B {C: st {x: i32}, x: B}, z: i32, y: A}"
+		);
+	}
+
+	#[test]
+	fn guard_arms() {
+		let source = "mt great_integer\n\teq 100\n\t\tprint \"equal\" great_integer\n\tlt 100\n\t\tprint \"lower\"\n\t5\n\t\tfive\n\t_\n\t\tother\n";
+		assert_eq!(
+			lowered(source),
+			"mt great_integer {?(eq 100) => (print \"equal\" great_integer); ?(lt 100) => (print \"lower\"); 5 => five; _ => other}"
+		);
+		let block = lower_source(source);
+		match &block.expressions[0].kind {
+			ExpressionKind::Node(Node::Fundamental(Fundamental::Match(matching))) => {
+				let catch_all = matching.catch_all().map(|x| show_arm(x));
+				assert_eq!(catch_all, Some(format!("_ => other")));
+			}
+			kind => panic!("lowered to {:?}", kind),
+		}
+	}
+
+	#[test]
+	fn type_arms() {
+		let source = "mt x_of_two_faces\n\tS64\n\t\tsigned\n\tF64 | F32\n\t\tfloat\n";
+		assert_eq!(
+			lowered(source),
+			"mt x_of_two_faces {:S64 => signed; :(F64 | F32) => float}"
+		);
+		match &lower_source(source).expressions[0].kind {
+			ExpressionKind::Node(Node::Fundamental(Fundamental::Match(matching))) => {
+				assert!(matching.catch_all().is_none());
+			}
+			kind => panic!("lowered to {:?}", kind),
+		}
+	}
+
+	#[test]
+	fn destructuring_arms() {
+		assert_eq!(
+			lowered("mt complex_structure\n\tst {a: S32 b: st {x: F32}}\n\t\tprint \"struct\" a x\n\tst\n\t\t\ta: S32\n\t\t\tb: [st x: F32]\n\t\tprint \"alternative\"\n"),
+			"mt complex_structure {:st {a: S32, b: st {x: F32}} => (print \"struct\" a x); :st {a: S32, b: st {x: F32}} => (print \"alternative\")}"
+		);
+	}
+
+	#[test]
+	fn functions() {
+		assert_eq!(
+			lowered("f :: fn a: S64 -> S64 => a + 1\ng :: fn 5 -> S64 => 10\nh :: fn spec: U32... -> U32 => spec"),
+			"f :: fn(a: S64) -> S64 => (a + 1)\ng :: fn(5) -> S64 => 10\nh :: fn(spec: U32...) -> U32 => spec"
+		);
+		assert_eq!(
+			lowered("i :: fn &_ -> _\nj :: fn $a: S64 => print a\nl :: fn -> S64"),
+			"i :: fn(&_) -> _\nj :: fn($a: S64) => (print a)\nl :: fn() -> S64"
+		);
+		assert_eq!(
+			lowered("k :: !fn a: S64 -> S64 => a\nm :: fn m: 'S64|'S32 -> S64 => 1"),
+			"k :: !fn(a: S64) -> S64 => a\nm :: fn(m: ('S64 | 'S32)) -> S64 => 1"
+		);
+	}
+
+	#[test]
+	fn traits() {
+		assert_eq!(
+			lowered("RangeAble :: tr\n\tnext: fn &% -> %\n\tfirst: fn -> %\n\tsize :: fn x: &% -> U64 => 0\n"),
+			"RangeAble :: tr {next: fn(&_: %) -> %, first: fn() -> %} {size :: fn(x: &%) -> U64 => 0}"
+		);
+		assert_eq!(
+			lowered("RangeAble :+ S64\n\tnext :: fn x: &S64 -> S64 => x + 1\n"),
+			"RangeAble :+ S64 {next :: fn(x: &S64) -> S64 => (x + 1)}"
+		);
+	}
+
+	#[test]
+	fn compile_time() {
+		assert_eq!(
+			lowered("build :: !fn a => a\ntsun : !Time.now\nx :! compute 5\n!mt arg\n\t1\n\t\t2\n"),
+			"build :: !fn(a) => a\ntsun : ! Time.now\nx :! ! (compute 5)\n!mt arg {1 => 2}"
+		);
+		let sources = [
+			("! y", 0),
+			("a :: !st a: S32", 5),
+			("b :: !!f", 6),
+			("c :: f!", 6),
+			("!im math", 0),
+		];
+		for (source, column) in &sources {
+			let info = parse_source(source.to_string(), &ParseOptions::default());
+			match info.errors.as_slice() {
+				[MisplacedCompileTime { context, .. }] => {
+					assert_eq!(context.location().column, *column, "{}", source)
+				}
+				errors => panic!("{} parsed with {:?}", source, errors),
+			}
+		}
+	}
+
+	#[test]
+	fn prefixes() {
+		assert_eq!(
+			lowered("a :: f $x &y 'S32\nb :: &[f x]\nc :: $a.b\nd :: fn &_ $y: &Self -> _"),
+			"a :: (f $x &y 'S32)\nb :: &(f x)\nc :: $a.b\nd :: fn(&_, $y: &Self) -> _"
+		);
+		let sources = [
+			("c :: $$x", "$", 5),
+			("d :: &$x", "&", 5),
+			("a = $&b", "$", 4),
+			("e :: $ x", "$", 5),
+			("f :: x$", "$", 6),
+			("h :: ''S32", "'", 5),
+		];
+		for (source, expected, column) in &sources {
+			let info = parse_source(source.to_string(), &ParseOptions::default());
+			match info.errors.as_slice() {
+				[InvalidPrefix {
+					context, prefix, ..
+				}] => {
+					assert_eq!(prefix, expected, "{}", source);
+					assert_eq!(context.location().column, *column, "{}", source);
+				}
+				errors => panic!("{} parsed with {:?}", source, errors),
+			}
+		}
+	}
+
+	#[test]
+	fn sum_declarations() {
+		assert_eq!(
+			lowered(
+				"x_of_two_faces : S64 | F64\ny : S64 | F64 = 5\nz : a + b\nw : S64 | F64 | F32\n"
+			),
+			"x_of_two_faces : (S64 | F64)\ny : (S64 | F64) = 5\nz : (a + b)\nw : (S64 | F64 | F32)"
+		);
+		match &lower_source("x_of_two_faces : S64 | F64").expressions[0].kind {
+			ExpressionKind::Binding(ast::Binding {
+				annotation: Some(Type::Sum { variants, .. }),
+				value: None,
+				..
+			}) => assert_eq!(variants.len(), 2),
+			kind => panic!("lowered to {:?}", kind),
+		}
+	}
+}
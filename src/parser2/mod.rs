@@ -9,23 +9,82 @@ use unicode_segmentation::UnicodeSegmentation;
 mod types;
 pub use types::*;
 
+mod syntax;
+pub use syntax::*;
+
 mod combinators;
 pub use combinators::*;
 
 mod values;
 pub use values::*;
 
-#[derive(Debug, Display)]
-pub struct SuccessInfo {
-	pub message: String,
+mod lexer;
+pub use lexer::*;
+
+mod literals;
+pub use literals::*;
+
+mod operators;
+pub use operators::*;
+
+mod desugar;
+pub use desugar::*;
+
+pub mod ast;
+
+mod lower;
+pub use lower::*;
+
+mod modules;
+pub use modules::*;
+
+mod tags;
+pub use tags::*;
+
+///
+/// Options for parsing a file
+///
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+	pub indentation: Indentation,
 }
-pub fn parse(input: std::path::PathBuf) -> Result<SuccessInfo, std::io::Error> {
+
+#[derive(Debug)]
+pub struct ParseInfo {
+	pub source: String,
+	pub block: ast::Block,
+	pub errors: Vec<ParseError>,
+}
+pub fn parse(input: &std::path::Path, options: &ParseOptions) -> Result<ParseInfo, std::io::Error> {
 	let source = std::fs::read_to_string(input)?;
-	let source = source.as_str().graphemes(true).collect::<Vec<_>>();
-	let input = &mut Input::new(source.into_iter());
-	let result = block(input);
-	let result = SuccessInfo {
-		message: format!("{:#?}", result),
+	Ok(parse_source(source, options))
+}
+
+pub fn parse_source(source: String, options: &ParseOptions) -> ParseInfo {
+	let graphemes = source.as_str().graphemes(true).collect::<Vec<_>>();
+	let (tokens, mut errors) =
+		tokenize(&mut Input::new(graphemes.into_iter()), options.indentation);
+	let input = &mut Input::new(tokens.iter().cloned());
+	let block = match block(input) {
+		Output::Ok(block) | Output::Partial { result: block, .. } => block,
+		Output::Error(error) | Output::Critical(error) => {
+			errors.push(error);
+			Block::None
+		}
 	};
-	Ok(result)
+	let block = resolve_operators(block, &OperatorTable::default());
+	errors.append(&mut block.errors());
+	match eof(input) {
+		Output::Error(error) | Output::Critical(error) => errors.push(error),
+		_ => {}
+	}
+	let (block, mut lowering_errors) = lower(desugar(block));
+	errors.append(&mut lowering_errors);
+	errors.append(&mut TagRegistry::with_builtins().dispatch(&block));
+	errors.sort_by_key(|x| x.location().map_or(usize::max_value(), |x| x.offset));
+	ParseInfo {
+		source,
+		block,
+		errors,
+	}
 }
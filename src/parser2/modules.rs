@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::ast::*;
+use super::types::*;
+use super::{parse, ParseInfo, ParseOptions};
+
+///
+/// `md "name" "version"` found in a parsed file
+///
+#[derive(Debug, Clone)]
+pub struct ModuleReference {
+	pub name: String,
+	pub version: Option<String>,
+	pub span: Span,
+	///
+	/// Referenced in the arm of a `mt`, the module is only required if the arm is taken
+	///
+	pub conditional: bool,
+}
+
+///
+/// Modules referenced in a block and the expressions nested in it
+///
+pub fn module_references(block: &Block) -> Vec<ModuleReference> {
+	let mut collector = References {
+		references: Vec::new(),
+		conditional: false,
+	};
+	collector.block(block);
+	collector.references
+}
+
+struct References {
+	references: Vec<ModuleReference>,
+	conditional: bool,
+}
+impl Visitor for References {
+	fn fundamental(&mut self, fundamental: &Fundamental) {
+		match fundamental {
+			Fundamental::Module(module) => self.references.push(ModuleReference {
+				name: module.name.clone(),
+				version: module.version.clone(),
+				span: module.span,
+				conditional: self.conditional,
+			}),
+			Fundamental::Match(matching) => {
+				self.node(&matching.value);
+				let conditional = std::mem::replace(&mut self.conditional, true);
+				matching.arms.iter().for_each(|x| self.arm(x));
+				self.conditional = conditional;
+			}
+			fundamental => walk_fundamental(self, fundamental),
+		}
+	}
+}
+
+#[derive(Debug)]
+pub struct LoadedModule {
+	pub path: PathBuf,
+	pub info: ParseInfo,
+	///
+	/// Paths of the loaded modules referenced by the file
+	///
+	pub dependencies: Vec<PathBuf>,
+}
+
+///
+/// Parses files and the modules they reference, each file is parsed once.
+/// Errors resolving or loading a module are reported in the file referencing it.
+///
+#[derive(Debug, Default)]
+pub struct ModuleLoader {
+	pub search_paths: Vec<PathBuf>,
+	pub options: ParseOptions,
+	modules: Vec<LoadedModule>,
+	loaded: HashMap<PathBuf, usize>,
+	loading: Vec<PathBuf>,
+}
+impl ModuleLoader {
+	pub fn new(search_paths: Vec<PathBuf>, options: ParseOptions) -> Self {
+		Self {
+			search_paths,
+			options,
+			..Self::default()
+		}
+	}
+
+	pub fn get(&self, path: &Path) -> Option<&LoadedModule> {
+		self.loaded.get(path).map(|x| &self.modules[*x])
+	}
+
+	///
+	/// Loaded modules, dependencies before the modules referencing them
+	///
+	pub fn modules(&self) -> &[LoadedModule] {
+		&self.modules
+	}
+
+	///
+	/// Load a file and the modules it references, returning its canonical path
+	///
+	pub fn load(&mut self, path: &Path) -> Result<PathBuf, std::io::Error> {
+		let path = path.canonicalize()?;
+		if self.loaded.contains_key(&path) {
+			return Ok(path);
+		}
+		let mut info = parse(&path, &self.options)?;
+		self.loading.push(path.clone());
+		let mut dependencies = Vec::new();
+		for reference in module_references(&info.block) {
+			let reason = match self.resolve(&path, &reference.name) {
+				None if reference.conditional => continue,
+				None => format!(
+					"`{}.ca` not found next to the file or in the search paths",
+					reference.name
+				),
+				Some(dependency) if self.loading.contains(&dependency) => {
+					let position = self.loading.iter().position(|x| *x == dependency);
+					let cycle = self.loading[position.unwrap_or_default()..]
+						.iter()
+						.chain(std::iter::once(&dependency))
+						.map(|x| {
+							format!("`{}`", x.file_name().unwrap_or_default().to_string_lossy())
+						})
+						.collect::<Vec<_>>();
+					format!("import cycle {}", cycle.join(" -> "))
+				}
+				Some(dependency) => match self.load(&dependency) {
+					Result::Ok(dependency) => {
+						dependencies.push(dependency);
+						continue;
+					}
+					Result::Err(error) => error.to_string(),
+				},
+			};
+			info.errors.push(ParseError::ModuleError {
+				context: InputContext::default().at(reference.span.start),
+				module: reference.name,
+				reason,
+			});
+		}
+		self.loading.pop();
+		info.errors
+			.sort_by_key(|x| x.location().map_or(usize::max_value(), |x| x.offset));
+		self.loaded.insert(path.clone(), self.modules.len());
+		self.modules.push(LoadedModule {
+			path: path.clone(),
+			info,
+			dependencies,
+		});
+		Ok(path)
+	}
+
+	///
+	/// Find `name.ca` next to the referencing file or in the search paths
+	///
+	pub fn resolve(&self, from: &Path, name: &str) -> Option<PathBuf> {
+		if name.is_empty() {
+			return None;
+		}
+		let file = format!("{}.ca", name);
+		from.parent()
+			.into_iter()
+			.chain(self.search_paths.iter().map(|x| x.as_path()))
+			.map(|x| x.join(&file))
+			.find(|x| x.is_file())
+			.and_then(|x| x.canonicalize().ok())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	///
+	/// Write the files into a fresh directory for the test
+	///
+	fn directory(test: &str, files: &[(&str, &str)]) -> PathBuf {
+		let directory =
+			std::env::temp_dir().join(format!("carina-modules-{}-{}", std::process::id(), test));
+		let _ = std::fs::remove_dir_all(&directory);
+		for (name, source) in files {
+			let path = directory.join(name);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, source).unwrap();
+		}
+		directory
+	}
+
+	fn reasons(module: &LoadedModule) -> Vec<String> {
+		module
+			.info
+			.errors
+			.iter()
+			.map(|x| match x {
+				ParseError::ModuleError { reason, .. } => reason.clone(),
+				error => panic!("unexpected error {:?}", error),
+			})
+			.collect()
+	}
+
+	#[test]
+	fn resolved_import() {
+		let directory = directory(
+			"resolved",
+			&[
+				("main.ca", "math :: md \"math\"\n"),
+				("math.ca", "x :: 1\n"),
+			],
+		);
+		let mut loader = ModuleLoader::default();
+		let main = loader.load(&directory.join("main.ca")).unwrap();
+		let math = directory.join("math.ca").canonicalize().unwrap();
+		let module = loader.get(&main).unwrap();
+		assert!(module.info.errors.is_empty());
+		assert_eq!(module.dependencies, vec![math.clone()]);
+		let paths = loader.modules().iter().map(|x| &x.path).collect::<Vec<_>>();
+		assert_eq!(paths, vec![&math, &main]);
+	}
+
+	#[test]
+	fn search_paths() {
+		let directory = directory(
+			"search",
+			&[
+				("main.ca", "rand :: md \"rand\"\n"),
+				("first/rand.ca", "x :: 1\n"),
+				("second/rand.ca", "x :: 2\n"),
+			],
+		);
+		let search_paths = vec![directory.join("first"), directory.join("second")];
+		let loader = ModuleLoader::new(search_paths, ParseOptions::default());
+		let first = directory.join("first/rand.ca").canonicalize().unwrap();
+		assert_eq!(
+			loader.resolve(&directory.join("main.ca"), "rand"),
+			Some(first)
+		);
+		std::fs::write(directory.join("rand.ca"), "x :: 0\n").unwrap();
+		let local = directory.join("rand.ca").canonicalize().unwrap();
+		assert_eq!(
+			loader.resolve(&directory.join("main.ca"), "rand"),
+			Some(local)
+		);
+	}
+
+	#[test]
+	fn cycles() {
+		let directory = directory(
+			"cycles",
+			&[("a.ca", "b :: md \"b\"\n"), ("b.ca", "a :: md \"a\"\n")],
+		);
+		let mut loader = ModuleLoader::default();
+		let a = loader.load(&directory.join("a.ca")).unwrap();
+		let b = directory.join("b.ca").canonicalize().unwrap();
+		assert!(reasons(loader.get(&a).unwrap()).is_empty());
+		assert_eq!(
+			reasons(loader.get(&b).unwrap()),
+			vec!["import cycle `a.ca` -> `b.ca` -> `a.ca`"]
+		);
+	}
+
+	#[test]
+	fn loaded_once() {
+		let directory = directory(
+			"once",
+			&[
+				("main.ca", "a :: md \"a\"\nb :: md \"b\"\n"),
+				("a.ca", "math :: md \"math\"\n"),
+				("b.ca", "math :: md \"math\"\n"),
+				("math.ca", "x :: 1\n"),
+			],
+		);
+		let mut loader = ModuleLoader::default();
+		loader.load(&directory.join("main.ca")).unwrap();
+		loader.load(&directory.join("math.ca")).unwrap();
+		assert_eq!(loader.modules().len(), 4);
+		let math = directory.join("math.ca").canonicalize().unwrap();
+		let dependents = loader
+			.modules()
+			.iter()
+			.filter(|x| x.dependencies.contains(&math))
+			.count();
+		assert_eq!(dependents, 2);
+	}
+
+	#[test]
+	fn missing_modules() {
+		let directory = directory(
+			"missing",
+			&[(
+				"main.ca",
+				"x :: 1\nnope :: md \"nope\"\nwindow :: mt os\n\t\"linux\"\n\t\tmd \"linux\"\n",
+			)],
+		);
+		let mut loader = ModuleLoader::default();
+		let main = loader.load(&directory.join("main.ca")).unwrap();
+		match loader.get(&main).unwrap().info.errors.as_slice() {
+			[ParseError::ModuleError {
+				context,
+				module,
+				reason,
+			}] => {
+				assert_eq!(module, "nope");
+				assert_eq!(
+					reason,
+					"`nope.ca` not found next to the file or in the search paths"
+				);
+				assert_eq!(context.location().line, 1);
+				assert_eq!(context.location().column, 8);
+			}
+			errors => panic!("loaded with {:?}", errors),
+		}
+		let references = module_references(&loader.get(&main).unwrap().info.block);
+		let conditional = references.iter().map(|x| x.conditional).collect::<Vec<_>>();
+		assert_eq!(conditional, vec![false, true]);
+	}
+}
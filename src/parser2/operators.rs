@@ -0,0 +1,610 @@
+use std::iter::Peekable;
+
+use super::combinators::*;
+use super::lexer::*;
+use super::literals::*;
+use super::syntax::*;
+use super::types::ParseError::*;
+use super::types::*;
+use super::values::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fixity {
+	Infix,
+	Prefix,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+	Left,
+	Right,
+	None,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operator {
+	pub symbol: String,
+	pub fixity: Fixity,
+	pub precedence: u8,
+	pub associativity: Associativity,
+}
+
+///
+/// Precedence of operators ranges from 0 to 9, higher precedence binds tighter.
+/// Juxtaposition binds tighter than any operator, so `f x + g y` is `[f x] + [g y]`.
+///
+pub const MAX_PRECEDENCE: u8 = 9;
+
+///
+/// Operators that are part of the grammar and can't be redefined
+///
+pub const RESERVED_OPERATORS: &[&str] = &[".", ",", "=", "..", "...", "->", "=>"];
+
+///
+/// Symbols separating the parts of declarations, operators don't extend across them
+///
+pub const SEPARATORS: &[&str] = &[":", "=", "->", "=>", "..."];
+
+#[derive(Debug, Clone)]
+pub struct OperatorTable {
+	operators: Vec<Operator>,
+}
+impl OperatorTable {
+	pub fn new() -> Self {
+		Self {
+			operators: Vec::new(),
+		}
+	}
+	///
+	/// Add an operator, replacing an existing operator with the same symbol and fixity
+	///
+	pub fn define(&mut self, operator: Operator) {
+		self.operators
+			.retain(|x| x.symbol != operator.symbol || x.fixity != operator.fixity);
+		self.operators.push(operator);
+	}
+	pub fn get(&self, symbol: &str, fixity: Fixity) -> Option<&Operator> {
+		self.operators
+			.iter()
+			.find(|x| x.symbol == symbol && x.fixity == fixity)
+	}
+	pub fn contains(&self, symbol: &str) -> bool {
+		self.operators.iter().any(|x| x.symbol == symbol)
+	}
+}
+impl Default for OperatorTable {
+	///
+	/// Operators defined by the specification: `=?` assumes equality, `|` declares exclusive and `+` inclusive unions
+	///
+	fn default() -> Self {
+		let mut table = Self::new();
+		for (symbol, precedence, associativity) in &[
+			("=?", 1, Associativity::None),
+			("|", 2, Associativity::Left),
+			("+", 3, Associativity::Left),
+		] {
+			table.define(Operator {
+				symbol: symbol.to_string(),
+				fixity: Fixity::Infix,
+				precedence: *precedence,
+				associativity: *associativity,
+			});
+		}
+		table
+	}
+}
+
+///
+/// Fold operators of the table into infix and prefix terms.
+/// `di "op" precedence associativity function` and `dp "op" precedence function` declarations
+/// extend the table for the rest of the enclosing block.
+///
+pub fn resolve_operators(block: Block, table: &OperatorTable) -> Block {
+	Resolver {
+		table: table.clone(),
+	}
+	.block(block)
+}
+
+///
+/// Binding and pipe operators, statements are split at them before operators are resolved
+///
+fn splits_statement(symbol: &str) -> bool {
+	BindingKind::from_operator(symbol).is_some() || PipeDirection::from_operator(symbol).is_some()
+}
+
+///
+/// Split a declared operator into the symbols the lexer reads it as
+///
+fn symbol_tokens(symbol: &str) -> Vec<&str> {
+	let mut tokens = Vec::new();
+	let mut rest = symbol;
+	while let Some(c) = rest.chars().next() {
+		let length = OPERATORS
+			.iter()
+			.find(|x| rest.starts_with(*x))
+			.map_or(c.len_utf8(), |x| x.len());
+		tokens.push(&rest[..length]);
+		rest = &rest[length..];
+	}
+	tokens
+}
+
+struct Resolver {
+	table: OperatorTable,
+}
+impl Resolver {
+	fn block(&mut self, block: Block) -> Block {
+		match block {
+			Block::Block { expressions, span } => {
+				let mut scope = Resolver {
+					table: self.table.clone(),
+				};
+				Block::Block {
+					expressions: expressions
+						.into_iter()
+						.map(|x| scope.expression(x))
+						.collect(),
+					span,
+				}
+			}
+			Block::None => Block::None,
+		}
+	}
+
+	fn expression(&mut self, expression: Expression) -> Expression {
+		match expression {
+			Expression::Expression {
+				mut terms,
+				comments,
+				block,
+				span,
+			} => {
+				self.declaration(&mut terms, span);
+				Expression::Expression {
+					terms: self.terms(terms),
+					comments,
+					block: self.block(block),
+					span,
+				}
+			}
+			Expression::Binding {
+				mut binding,
+				comments,
+				block,
+				span,
+			} => {
+				self.declaration(&mut binding.rhs, span);
+				Expression::Binding {
+					binding: Binding {
+						lhs: self.terms(binding.lhs),
+						annotation: binding.annotation.map(|x| self.terms(x)),
+						rhs: self.terms(binding.rhs),
+						..binding
+					},
+					comments,
+					block: self.block(block),
+					span,
+				}
+			}
+			Expression::Error { .. } => expression,
+		}
+	}
+
+	///
+	/// Register a `di` or `dp` declaration, replacing invalid parts with error terms
+	///
+	fn declaration(&mut self, terms: &mut Vec<Term>, span: Span) {
+		let (declaration, fixity) = match terms.first() {
+			Some(Term::Identifier { name, .. }) if name == "di" => ("di", Fixity::Infix),
+			Some(Term::Identifier { name, .. }) if name == "dp" => ("dp", Fixity::Prefix),
+			_ => return,
+		};
+		let mut valid = true;
+		let mut check = |terms: &mut Vec<Term>, index: usize, reason: Option<String>| {
+			let reason = match reason {
+				Some(reason) => reason,
+				None => return,
+			};
+			let location = match terms.get(index) {
+				Some(term) => term.span(),
+				None => Span::new(span.end, span.end),
+			};
+			let error = Term::Error {
+				error: InvalidDeclaration {
+					context: InputContext::default().at(location.start),
+					declaration: declaration.to_string(),
+					reason,
+				},
+				span: location,
+			};
+			match index < terms.len() {
+				true => terms[index] = error,
+				false => terms.push(error),
+			}
+			valid = false;
+		};
+
+		let symbol = match terms.get(1) {
+			Some(Term::Literal {
+				literal: Literal::String(symbol),
+				..
+			}) => symbol.clone(),
+			_ => String::new(),
+		};
+		let reason = if symbol.is_empty() {
+			Some(format!("expected the operator as a string"))
+		} else if !symbol.chars().all(|c| is_operator_char(c.to_string())) {
+			Some(format!("operators consist of special characters only"))
+		} else if RESERVED_OPERATORS.contains(&symbol.as_str()) || splits_statement(&symbol) {
+			Some(format!("`{}` is reserved", symbol))
+		} else if let Some(part) = symbol_tokens(&symbol)
+			.into_iter()
+			.find(|x| splits_statement(x))
+		{
+			Some(format!("`{}` would be split at `{}`", symbol, part))
+		} else {
+			None
+		};
+		check(terms, 1, reason);
+
+		let precedence = match terms.get(2) {
+			Some(Term::Literal {
+				literal: Literal::Integer(precedence),
+				..
+			}) if *precedence >= 0 && *precedence <= MAX_PRECEDENCE as i128 => Some(*precedence as u8),
+			_ => None,
+		};
+		let reason = format!("expected the precedence from 0 to {}", MAX_PRECEDENCE);
+		check(terms, 2, precedence.map_or(Some(reason), |_| None));
+
+		let mut associativity = Associativity::Right;
+		if fixity == Fixity::Infix {
+			associativity = match terms.get(3) {
+				Some(Term::Identifier { name, .. }) if name == "left" => Associativity::Left,
+				Some(Term::Identifier { name, .. }) if name == "right" => Associativity::Right,
+				Some(Term::Identifier { name, .. }) if name == "none" => Associativity::None,
+				_ => {
+					let reason = format!("expected the associativity `left`, `right` or `none`");
+					check(terms, 3, Some(reason));
+					Associativity::None
+				}
+			};
+		}
+		let function = if fixity == Fixity::Infix { 4 } else { 3 };
+		if terms.len() <= function {
+			check(
+				terms,
+				function,
+				Some(format!("expected the operator function")),
+			);
+		}
+
+		if valid {
+			self.table.define(Operator {
+				symbol,
+				fixity,
+				precedence: precedence.unwrap_or_default(),
+				associativity,
+			});
+		}
+	}
+
+	///
+	/// Fold operators between separators, a name followed by `:` starts a new part as in `a: S32 | S64 b: F64`
+	///
+	fn terms(&mut self, terms: Vec<Term>) -> Vec<Term> {
+		let terms = self.join_symbols(terms);
+		let mut resolved = Vec::new();
+		let mut part = Vec::new();
+		let mut terms = terms.into_iter().peekable();
+		while let Some(term) = terms.next() {
+			let separator = match (&term, terms.peek()) {
+				(Term::Symbol { text, .. }, _) => {
+					SEPARATORS.contains(&text.as_str())
+						|| BindingKind::from_operator(text).is_some()
+				}
+				(Term::Identifier { .. }, Some(Term::Symbol { text, .. })) => text == ":",
+				_ => false,
+			};
+			if !separator {
+				part.push(term);
+				continue;
+			}
+			let part = std::mem::replace(&mut part, Vec::new());
+			resolved.extend(self.operation(&mut part.into_iter().peekable(), 0));
+			resolved.push(self.term(term));
+		}
+		resolved.extend(self.operation(&mut part.into_iter().peekable(), 0));
+		resolved
+	}
+
+	///
+	/// Join adjacent symbols forming an operator that is lexed as multiple tokens
+	///
+	fn join_symbols(&self, mut terms: Vec<Term>) -> Vec<Term> {
+		let mut index = 0;
+		while index < terms.len() {
+			let mut text = String::new();
+			let mut end = None;
+			let mut joined = None;
+			for (offset, term) in terms[index..].iter().enumerate() {
+				match term {
+					Term::Symbol { text: part, span }
+						if end.map_or(true, |end: Location| end.offset == span.start.offset) =>
+					{
+						text.push_str(part);
+						end = Some(span.end);
+						if offset > 0 && self.table.contains(&text) {
+							joined = Some((offset, text.clone(), span.end));
+						}
+					}
+					_ => break,
+				}
+			}
+			if let Some((offset, text, end)) = joined {
+				let start = terms[index].span().start;
+				let symbol = Term::Symbol {
+					text,
+					span: Span::new(start, end),
+				};
+				terms.splice(index..=index + offset, std::iter::once(symbol));
+			}
+			index += 1;
+		}
+		terms
+	}
+
+	fn operator(&self, term: Option<&Term>, fixity: Fixity) -> Option<Operator> {
+		match term {
+			Some(Term::Symbol { text, .. }) => self.table.get(text, fixity).cloned(),
+			_ => None,
+		}
+	}
+
+	///
+	/// Parse operands joined by infix operators binding at least as tight as `precedence`
+	///
+	fn operation(
+		&mut self,
+		terms: &mut Peekable<impl Iterator<Item = Term>>,
+		precedence: u8,
+	) -> Vec<Term> {
+		let mut lhs = self.operand(terms);
+		let mut previous: Option<Operator> = None;
+		while let Some(operator) = self.operator(terms.peek(), Fixity::Infix) {
+			if operator.precedence < precedence {
+				break;
+			}
+			let symbol = terms.next().map(|x| x.span()).unwrap_or_default();
+			let mut first_error = None;
+			require(
+				&mut lhs,
+				InputContext::default().at(symbol.start),
+				format!("expression before `{}`", operator.symbol),
+				operator.symbol.clone(),
+				&mut first_error,
+			);
+			let next = match operator.associativity {
+				Associativity::Right => operator.precedence,
+				_ => operator.precedence + 1,
+			};
+			let mut rhs = self.operation(terms, next);
+			require(
+				&mut rhs,
+				InputContext::default().at(symbol.end),
+				format!("expression after `{}`", operator.symbol),
+				format!("{}", TokenKind::Newline),
+				&mut first_error,
+			);
+			if operator.associativity == Associativity::None && previous.as_ref() == Some(&operator)
+			{
+				lhs = vec![Term::Error {
+					error: NonAssociative {
+						context: InputContext::default().at(symbol.start),
+						operator: operator.symbol.clone(),
+					},
+					span: symbol,
+				}];
+			}
+			let span = terms_span(&lhs)
+				.unwrap_or(symbol)
+				.to(terms_span(&rhs).unwrap_or(symbol));
+			lhs = vec![Term::Infix {
+				operator: operator.symbol.clone(),
+				lhs,
+				rhs,
+				span,
+			}];
+			previous = Some(operator);
+		}
+		lhs
+	}
+
+	///
+	/// Parse juxtaposed terms up to the next infix operator, applying prefix operators to the rest of the operand
+	///
+	fn operand(&mut self, terms: &mut Peekable<impl Iterator<Item = Term>>) -> Vec<Term> {
+		let mut operand = Vec::new();
+		loop {
+			if !operand.is_empty() && self.operator(terms.peek(), Fixity::Infix).is_some() {
+				break;
+			}
+			if let Some(operator) = self.operator(terms.peek(), Fixity::Prefix) {
+				let symbol = terms.next().map(|x| x.span()).unwrap_or_default();
+				let mut inner = self.operation(terms, operator.precedence);
+				let mut first_error = None;
+				require(
+					&mut inner,
+					InputContext::default().at(symbol.end),
+					format!("expression after `{}`", operator.symbol),
+					format!("{}", TokenKind::Newline),
+					&mut first_error,
+				);
+				let span = symbol.to(terms_span(&inner).unwrap_or(symbol));
+				operand.push(Term::Prefix {
+					operator: operator.symbol,
+					operand: inner,
+					span,
+				});
+				continue;
+			}
+			if self.operator(terms.peek(), Fixity::Infix).is_some() {
+				break;
+			}
+			match terms.next() {
+				Some(term) => operand.push(self.term(term)),
+				None => break,
+			}
+		}
+		operand
+	}
+
+	fn term(&mut self, term: Term) -> Term {
+		match term {
+			Term::Subexpression { terms, span } => Term::Subexpression {
+				terms: self.terms(terms),
+				span,
+			},
+			Term::Group { terms, span } => Term::Group {
+				terms: self.terms(terms),
+				span,
+			},
+			Term::Block { block, span } => Term::Block {
+				block: self.block(block),
+				span,
+			},
+			Term::Pipe {
+				direction,
+				lhs,
+				rhs,
+				operator,
+				span,
+			} => Term::Pipe {
+				direction,
+				lhs: self.terms(lhs),
+				rhs: self.terms(rhs),
+				operator,
+				span,
+			},
+			Term::Range {
+				start,
+				end,
+				inclusive,
+				span,
+			} => Term::Range {
+				start: Box::new(self.term(*start)),
+				end: Box::new(self.term(*end)),
+				inclusive,
+				span,
+			},
+			Term::Spread { operand, span } => Term::Spread {
+				operand: Box::new(self.term(*operand)),
+				span,
+			},
+			term => term,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use unicode_segmentation::UnicodeSegmentation;
+
+	use super::*;
+
+	///
+	/// Terms of the first expression with the operators resolved
+	///
+	fn resolve(source: &str) -> Vec<Term> {
+		let graphemes = source.graphemes(true).collect::<Vec<_>>();
+		let (tokens, _) = tokenize(&mut Input::new(graphemes.into_iter()), Indentation::Detect);
+		let block = match block(&mut Input::new(tokens.into_iter())) {
+			Output::Ok(block) | Output::Partial { result: block, .. } => block,
+			output => panic!("parsed to {:?}", output),
+		};
+		match resolve_operators(block, &OperatorTable::default()) {
+			Block::Block { expressions, .. } => match expressions.into_iter().last() {
+				Some(Expression::Expression { terms, .. }) => terms,
+				expression => panic!("parsed to {:?}", expression),
+			},
+			Block::None => Vec::new(),
+		}
+	}
+
+	fn show(terms: &[Term]) -> String {
+		let terms = terms
+			.iter()
+			.map(|x| match x {
+				Term::Identifier { name, .. } => name.clone(),
+				Term::Infix {
+					operator, lhs, rhs, ..
+				} => format!("({} {} {})", show(lhs), operator, show(rhs)),
+				Term::Prefix {
+					operator, operand, ..
+				} => format!("({}{})", operator, show(operand)),
+				Term::Error { error, .. } => format!("<{:?}>", error),
+				term => format!("{:?}", term),
+			})
+			.collect::<Vec<_>>();
+		terms.join(" ")
+	}
+
+	#[test]
+	fn precedence() {
+		assert_eq!(show(&resolve("a | b + c d")), "(a | (b + c d))");
+		assert_eq!(show(&resolve("a + b | c")), "((a + b) | c)");
+		assert_eq!(
+			show(&resolve("dp \"~\" 2 f\na + ~ b | c")),
+			"(a + (~(b | c)))"
+		);
+	}
+
+	#[test]
+	fn associativity() {
+		assert_eq!(show(&resolve("a + b + c")), "((a + b) + c)");
+		assert_eq!(
+			show(&resolve("di \"^\" 5 right pow\na ^ b ^ c + d")),
+			"((a ^ (b ^ c)) + d)"
+		);
+		assert_eq!(show(&resolve("di \"^\" 5 none pow\na ^ b")), "(a ^ b)");
+	}
+
+	#[test]
+	fn non_associative() {
+		match resolve("a =? b =? c").as_slice() {
+			[Term::Infix { lhs, .. }] => match lhs.as_slice() {
+				[Term::Error {
+					error: NonAssociative { operator, .. },
+					..
+				}] => assert_eq!(operator, "=?"),
+				lhs => panic!("resolved to {}", show(lhs)),
+			},
+			terms => panic!("resolved to {}", show(terms)),
+		}
+	}
+
+	#[test]
+	fn declarations() {
+		for (declaration, reason) in &[
+			("di \":>\" 5 left f", "`:>` would be split at `:`"),
+			("di \"<|>\" 5 left f", "`<|>` would be split at `<|`"),
+			("di \"=:>\" 5 left f", "`=:>` would be split at `=:`"),
+			("di \"|>\" 5 left f", "`|>` is reserved"),
+			("di \"<>\" 10 left f", "expected the precedence from 0 to 9"),
+		] {
+			let terms = resolve(declaration);
+			match terms.iter().find(|x| match x {
+				Term::Error { .. } => true,
+				_ => false,
+			}) {
+				Some(Term::Error {
+					error: InvalidDeclaration { reason: found, .. },
+					..
+				}) => assert_eq!(found, reason),
+				_ => panic!("{} resolved to {}", declaration, show(&terms)),
+			}
+		}
+		assert_eq!(show(&resolve("di \"<=>\" 5 left f\na <=> b")), "(a <=> b)");
+	}
+}
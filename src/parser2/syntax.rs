@@ -0,0 +1,386 @@
+use super::lexer::*;
+use super::literals::*;
+use super::types::*;
+
+#[derive(Debug, Clone)]
+pub enum Block {
+	Block {
+		expressions: Vec<Expression>,
+		span: Span,
+	},
+	None,
+}
+impl Block {
+	///
+	/// Errors recorded in the block and all nested blocks, in source order
+	///
+	pub fn errors(&self) -> Vec<ParseError> {
+		let mut errors = Vec::new();
+		if let Block::Block { expressions, .. } = self {
+			for expression in expressions {
+				match expression {
+					Expression::Expression { terms, block, .. } => {
+						for term in terms {
+							term.collect_errors(&mut errors);
+						}
+						errors.append(&mut block.errors());
+					}
+					Expression::Binding { binding, block, .. } => {
+						let sides = [
+							Some(&binding.lhs),
+							binding.annotation.as_ref(),
+							Some(&binding.rhs),
+						];
+						for term in sides.iter().flatten().flat_map(|terms| terms.iter()) {
+							term.collect_errors(&mut errors);
+						}
+						errors.append(&mut block.errors());
+					}
+					Expression::Error { error, .. } => errors.push(error.clone()),
+				}
+			}
+		}
+		errors
+	}
+}
+
+#[derive(Debug, Clone)]
+pub enum Term {
+	Identifier {
+		name: String,
+		span: Span,
+	},
+	Literal {
+		literal: Literal,
+		span: Span,
+	},
+	Symbol {
+		text: String,
+		span: Span,
+	},
+	///
+	/// `[...]`, evaluated before the enclosing expression
+	///
+	Subexpression {
+		terms: Vec<Term>,
+		span: Span,
+	},
+	///
+	/// `(...)`
+	///
+	Group {
+		terms: Vec<Term>,
+		span: Span,
+	},
+	///
+	/// `{...}` with expressions separated by `,`
+	///
+	Block {
+		block: Block,
+		span: Span,
+	},
+	///
+	/// `lhs |> rhs` or `lhs <| rhs`, rewritten into subexpressions by `desugar`
+	///
+	Pipe {
+		direction: PipeDirection,
+		lhs: Vec<Term>,
+		rhs: Vec<Term>,
+		operator: Span,
+		span: Span,
+	},
+	///
+	/// Operator from the operator table between two operands
+	///
+	Infix {
+		operator: String,
+		lhs: Vec<Term>,
+		rhs: Vec<Term>,
+		span: Span,
+	},
+	///
+	/// Operator from the operator table before its operand
+	///
+	Prefix {
+		operator: String,
+		operand: Vec<Term>,
+		span: Span,
+	},
+	///
+	/// `start..end` or `start..=end` including the end, written without spaces
+	///
+	Range {
+		start: Box<Term>,
+		end: Box<Term>,
+		inclusive: bool,
+		span: Span,
+	},
+	///
+	/// `..container` written without spaces, spreading the container into its elements
+	///
+	Spread {
+		operand: Box<Term>,
+		span: Span,
+	},
+	Error {
+		error: ParseError,
+		span: Span,
+	},
+}
+impl Term {
+	pub fn span(&self) -> Span {
+		match self {
+			Term::Identifier { span, .. }
+			| Term::Literal { span, .. }
+			| Term::Symbol { span, .. }
+			| Term::Subexpression { span, .. }
+			| Term::Group { span, .. }
+			| Term::Block { span, .. }
+			| Term::Range { span, .. }
+			| Term::Spread { span, .. }
+			| Term::Pipe { span, .. }
+			| Term::Infix { span, .. }
+			| Term::Prefix { span, .. }
+			| Term::Error { span, .. } => *span,
+		}
+	}
+	fn collect_errors(&self, errors: &mut Vec<ParseError>) {
+		match self {
+			Term::Subexpression { terms, .. } | Term::Group { terms, .. } => {
+				for term in terms {
+					term.collect_errors(errors);
+				}
+			}
+			Term::Block { block, .. } => errors.append(&mut block.errors()),
+			Term::Pipe { lhs, rhs, .. } | Term::Infix { lhs, rhs, .. } => {
+				for term in lhs.iter().chain(rhs.iter()) {
+					term.collect_errors(errors);
+				}
+			}
+			Term::Prefix { operand, .. } => {
+				for term in operand {
+					term.collect_errors(errors);
+				}
+			}
+			Term::Range { start, end, .. } => {
+				start.collect_errors(errors);
+				end.collect_errors(errors);
+			}
+			Term::Spread { operand, .. } => operand.collect_errors(errors),
+			Term::Error { error, .. } => errors.push(error.clone()),
+			_ => {}
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipeDirection {
+	/// `|>`, passes the preceding expression to the succeeding one
+	Forward,
+	/// `<|`, passes the succeeding expression to the preceding one
+	Backward,
+}
+impl PipeDirection {
+	pub fn operator(self) -> &'static str {
+		match self {
+			PipeDirection::Forward => "|>",
+			PipeDirection::Backward => "<|",
+		}
+	}
+	pub fn from_operator(operator: &str) -> Option<Self> {
+		match operator {
+			"|>" => Some(PipeDirection::Forward),
+			"<|" => Some(PipeDirection::Backward),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Comment {
+	pub text: String,
+	pub span: Span,
+}
+impl From<Token> for Comment {
+	fn from(token: Token) -> Self {
+		Self {
+			text: token.text.trim_start_matches('#').trim_end().to_string(),
+			span: token.span,
+		}
+	}
+}
+
+///
+/// Comments attached to an expression: lines preceding it, a comment at the end of its line,
+/// and lines at the end of the enclosing block not followed by another expression
+///
+#[derive(Debug, Clone, Default)]
+pub struct Comments {
+	pub leading: Vec<Comment>,
+	pub trailing: Option<Comment>,
+	pub dangling: Vec<Comment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FundamentalKind {
+	/// `pd`
+	Plaindata,
+	/// `st`
+	Structure,
+	/// `fn`
+	Function,
+	/// `tr`
+	Trait,
+	/// `en`
+	Enumeration,
+	/// `md`
+	Module,
+	/// `mt`
+	Match,
+}
+impl FundamentalKind {
+	pub const ALL: &'static [FundamentalKind] = &[
+		FundamentalKind::Plaindata,
+		FundamentalKind::Structure,
+		FundamentalKind::Function,
+		FundamentalKind::Trait,
+		FundamentalKind::Enumeration,
+		FundamentalKind::Module,
+		FundamentalKind::Match,
+	];
+	pub fn keyword(self) -> &'static str {
+		match self {
+			FundamentalKind::Plaindata => "pd",
+			FundamentalKind::Structure => "st",
+			FundamentalKind::Function => "fn",
+			FundamentalKind::Trait => "tr",
+			FundamentalKind::Enumeration => "en",
+			FundamentalKind::Module => "md",
+			FundamentalKind::Match => "mt",
+		}
+	}
+	pub fn from_keyword(keyword: &str) -> Option<Self> {
+		Self::ALL.iter().copied().find(|x| x.keyword() == keyword)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingKind {
+	/// `:`
+	Declare,
+	/// `::`
+	Define,
+	/// `:=`
+	Assign,
+	/// `:!`
+	AssignCompileTime,
+	/// `:+`
+	Add,
+	/// `:-`
+	Remove,
+	/// `=:`
+	Replace,
+	/// `<-`
+	Store,
+}
+impl BindingKind {
+	pub const ALL: &'static [BindingKind] = &[
+		BindingKind::Declare,
+		BindingKind::Define,
+		BindingKind::Assign,
+		BindingKind::AssignCompileTime,
+		BindingKind::Add,
+		BindingKind::Remove,
+		BindingKind::Replace,
+		BindingKind::Store,
+	];
+	pub fn operator(self) -> &'static str {
+		match self {
+			BindingKind::Declare => ":",
+			BindingKind::Define => "::",
+			BindingKind::Assign => ":=",
+			BindingKind::AssignCompileTime => ":!",
+			BindingKind::Add => ":+",
+			BindingKind::Remove => ":-",
+			BindingKind::Replace => "=:",
+			BindingKind::Store => "<-",
+		}
+	}
+	pub fn from_operator(operator: &str) -> Option<Self> {
+		Self::ALL.iter().copied().find(|x| x.operator() == operator)
+	}
+}
+
+///
+/// Statement split at its binding operator, e.g. `name : Type = value`
+///
+#[derive(Debug, Clone)]
+pub struct Binding {
+	pub kind: BindingKind,
+	pub lhs: Vec<Term>,
+	pub annotation: Option<Vec<Term>>,
+	pub rhs: Vec<Term>,
+	pub operator: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Expression {
+	Expression {
+		terms: Vec<Term>,
+		comments: Comments,
+		block: Block,
+		span: Span,
+	},
+	Binding {
+		binding: Binding,
+		comments: Comments,
+		block: Block,
+		span: Span,
+	},
+	Error {
+		error: ParseError,
+		span: Span,
+	},
+}
+impl Expression {
+	///
+	/// Span including the nested block
+	///
+	pub fn full_span(&self) -> Span {
+		match self {
+			Expression::Expression {
+				block: Block::Block { span: block, .. },
+				span,
+				..
+			}
+			| Expression::Binding {
+				block: Block::Block { span: block, .. },
+				span,
+				..
+			} => span.to(*block),
+			Expression::Expression { span, .. }
+			| Expression::Binding { span, .. }
+			| Expression::Error { span, .. } => *span,
+		}
+	}
+	pub fn comments_mut(&mut self) -> Option<&mut Comments> {
+		match self {
+			Expression::Expression { comments, .. } | Expression::Binding { comments, .. } => {
+				Some(comments)
+			}
+			Expression::Error { .. } => None,
+		}
+	}
+	pub fn attach_leading(&mut self, leading: &mut Vec<Comment>) {
+		if let Some(comments) = self.comments_mut() {
+			comments.leading.append(leading);
+		}
+	}
+}
+
+///
+/// Span from the first to the last term
+///
+pub fn terms_span(terms: &[Term]) -> Option<Span> {
+	Some(terms.first()?.span().to(terms.last()?.span()))
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use super::ast::*;
+use super::types::*;
+
+///
+/// Expression, structure or trait member, or enumeration variant a tag is attached to
+///
+#[derive(Debug, Clone, Copy)]
+pub enum Tagged<'t> {
+	Expression(&'t Expression),
+	Member(&'t Member),
+	Variant(&'t Variant),
+}
+impl Tagged<'_> {
+	pub fn span(&self) -> Span {
+		match self {
+			Tagged::Expression(expression) => expression.span,
+			Tagged::Member(member) => member.span,
+			Tagged::Variant(variant) => variant.span,
+		}
+	}
+}
+
+impl Tag {
+	///
+	/// Error for an invalid tag, reported at the tag
+	///
+	pub fn invalid(&self, reason: &str) -> ParseError {
+		ParseError::InvalidTag {
+			context: InputContext::default().at(self.span.start),
+			tag: self.name.name.clone(),
+			reason: reason.to_string(),
+		}
+	}
+
+	fn require_arguments(&self, reason: &str) -> Vec<ParseError> {
+		if self.arguments.is_empty() {
+			vec![self.invalid(reason)]
+		} else {
+			Vec::new()
+		}
+	}
+}
+
+type TagHandler<'a> = Box<dyn FnMut(&Tag, Tagged) -> Vec<ParseError> + 'a>;
+
+///
+/// Handlers subscribed to tags by name, called for each tagged expression in source order
+///
+#[derive(Default)]
+pub struct TagRegistry<'a> {
+	handlers: HashMap<String, Vec<TagHandler<'a>>>,
+}
+impl<'a> TagRegistry<'a> {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	///
+	/// Registry checking the tags of the specification, `@assume` and `@define` take the assumption or definition
+	///
+	pub fn with_builtins() -> Self {
+		let mut registry = Self::new();
+		registry.subscribe("assume", |tag, _| {
+			tag.require_arguments("expected the assumption")
+		});
+		registry.subscribe("define", |tag, _| {
+			tag.require_arguments("expected the definition")
+		});
+		registry
+	}
+
+	///
+	/// Call the handler for every `@name` tag, after the handlers subscribed before it
+	///
+	pub fn subscribe(
+		&mut self,
+		name: &str,
+		handler: impl FnMut(&Tag, Tagged) -> Vec<ParseError> + 'a,
+	) {
+		self.handlers
+			.entry(name.to_string())
+			.or_default()
+			.push(Box::new(handler));
+	}
+
+	pub fn is_subscribed(&self, name: &str) -> bool {
+		self.handlers.contains_key(name)
+	}
+
+	///
+	/// Pass the tags in the block and its nested expressions, members and variants to the subscribed handlers,
+	/// returning their errors
+	///
+	pub fn dispatch(&mut self, block: &Block) -> Vec<ParseError> {
+		let mut dispatcher = Dispatcher {
+			handlers: &mut self.handlers,
+			errors: Vec::new(),
+		};
+		dispatcher.block(block);
+		dispatcher.errors
+	}
+}
+
+struct Dispatcher<'r, 'a> {
+	handlers: &'r mut HashMap<String, Vec<TagHandler<'a>>>,
+	errors: Vec<ParseError>,
+}
+impl Dispatcher<'_, '_> {
+	fn dispatch(&mut self, tags: &[Tag], tagged: Tagged) {
+		for tag in tags {
+			if let Some(handlers) = self.handlers.get_mut(&tag.name.name) {
+				for handler in handlers {
+					self.errors.extend(handler(tag, tagged));
+				}
+			}
+		}
+	}
+}
+impl Visitor for Dispatcher<'_, '_> {
+	fn expression(&mut self, expression: &Expression) {
+		self.dispatch(&expression.tags, Tagged::Expression(expression));
+		walk_expression(self, expression)
+	}
+	fn member(&mut self, member: &Member) {
+		self.dispatch(&member.tags, Tagged::Member(member));
+		walk_member(self, member)
+	}
+	fn variant(&mut self, variant: &Variant) {
+		self.dispatch(&variant.tags, Tagged::Variant(variant));
+		walk_variant(self, variant)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::{parse_source, ParseOptions};
+	use super::*;
+
+	#[test]
+	fn builtins() {
+		let source = "@define\nx := 1\n@assume a\ny := 0x\n@assume\nst\n\ta: S32 @define\n";
+		let info = parse_source(source.to_string(), &ParseOptions::default());
+		let errors = info
+			.errors
+			.iter()
+			.map(|x| match x {
+				ParseError::InvalidTag { tag, reason, .. } => format!("{}: {}", tag, reason),
+				error => format!("{:?}", error.location().map(|x| x.line)),
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(
+			errors,
+			vec![
+				"define: expected the definition",
+				"Some(3)",
+				"assume: expected the assumption",
+				"define: expected the definition",
+			]
+		);
+	}
+
+	#[test]
+	fn dispatch() {
+		let info = parse_source(
+			"@note a\nx := 1\nen\n\tA @note b\n".to_string(),
+			&ParseOptions::default(),
+		);
+		let mut seen = Vec::new();
+		let mut registry = TagRegistry::new();
+		registry.subscribe("note", |tag, tagged| {
+			let kind = match tagged {
+				Tagged::Expression(_) => "expression",
+				Tagged::Member(_) => "member",
+				Tagged::Variant(_) => "variant",
+			};
+			seen.push((kind, tag.arguments.len()));
+			Vec::new()
+		});
+		assert!(registry.dispatch(&info.block).is_empty());
+		drop(registry);
+		assert_eq!(seen, vec![("expression", 1), ("variant", 1)]);
+	}
+}
@@ -9,7 +9,7 @@ use itertools::*;
 
 use super::values::*;
 
-pub trait InputIterItem = Debug + Display + Clone + AsRef<str> + PartialEq + Eq;
+pub trait InputIterItem = Debug + Display + Clone + AsRef<str> + PartialEq + Eq + Advance;
 pub trait InputIter<T: InputIterItem> = Debug + Clone + Iterator<Item = T>;
 
 #[derive(Debug, Clone)]
@@ -24,7 +24,78 @@ pub enum ParseError {
 		expected: String,
 		found: String,
 	},
-	NoneMatched,
+	UnexpectedToken {
+		context: InputContext,
+		expected: String,
+		found: String,
+	},
+	InvalidLiteral {
+		context: InputContext,
+		literal: String,
+		reason: String,
+	},
+	LiteralOverflow {
+		context: InputContext,
+		literal: String,
+		bounds: String,
+	},
+	InvalidDeclaration {
+		context: InputContext,
+		declaration: String,
+		reason: String,
+	},
+	NonAssociative {
+		context: InputContext,
+		operator: String,
+	},
+	ModuleError {
+		context: InputContext,
+		module: String,
+		reason: String,
+	},
+	InvalidTag {
+		context: InputContext,
+		tag: String,
+		reason: String,
+	},
+	MisplacedCompileTime {
+		context: InputContext,
+		reason: String,
+	},
+	InvalidPrefix {
+		context: InputContext,
+		prefix: String,
+		reason: String,
+	},
+	InconsistentIndentation {
+		context: InputContext,
+		expected: String,
+		found: String,
+	},
+	NoneMatched {
+		context: InputContext,
+	},
+}
+
+impl ParseError {
+	pub fn location(&self) -> Option<Location> {
+		match self {
+			ParseError::UnexpectedEOF { context, .. }
+			| ParseError::UnexpectedCharacter { context, .. }
+			| ParseError::UnexpectedToken { context, .. }
+			| ParseError::InvalidLiteral { context, .. }
+			| ParseError::LiteralOverflow { context, .. }
+			| ParseError::InvalidDeclaration { context, .. }
+			| ParseError::NonAssociative { context, .. }
+			| ParseError::ModuleError { context, .. }
+			| ParseError::InvalidTag { context, .. }
+			| ParseError::MisplacedCompileTime { context, .. }
+			| ParseError::InvalidPrefix { context, .. }
+			| ParseError::InconsistentIndentation { context, .. }
+			| ParseError::NoneMatched { context } => Some(context.location()),
+			ParseError::Unspecified => None,
+		}
+	}
 }
 
 #[derive(Debug, Clone)]
@@ -35,6 +106,26 @@ pub enum Output<R> {
 	Critical(ParseError),
 }
 impl<R> Output<R> {
+	///
+	/// Ok, or Partial if a recoverable error occurred while producing the result
+	///
+	pub fn partial(result: R, error: Option<ParseError>) -> Self {
+		match error {
+			Some(error) => Self::Partial { result, error },
+			None => Self::Ok(result),
+		}
+	}
+	///
+	/// Move a recoverable error into the result so it survives `?`
+	///
+	pub fn recoverable(self) -> Output<(R, Option<ParseError>)> {
+		match self {
+			Self::Ok(x) => Output::Ok((x, None)),
+			Self::Partial { result, error } => Output::Ok((result, Some(error))),
+			Self::Error(x) => Output::Error(x),
+			Self::Critical(x) => Output::Critical(x),
+		}
+	}
 	pub fn map_value<T: From<R>>(self) -> Output<T> {
 		match self {
 			Self::Ok(x) => Output::Ok(x.into()),
@@ -46,6 +137,29 @@ impl<R> Output<R> {
 			Self::Critical(x) => Output::Critical(x),
 		}
 	}
+	pub fn map<T>(self, mapper: impl FnOnce(R) -> T) -> Output<T> {
+		match self {
+			Self::Ok(x) => Output::Ok(mapper(x)),
+			Self::Partial { result, error } => Output::Partial {
+				result: mapper(result),
+				error,
+			},
+			Self::Error(x) => Output::Error(x),
+			Self::Critical(x) => Output::Critical(x),
+		}
+	}
+	pub fn is_ok(&self) -> bool {
+		match self {
+			Self::Ok(_) => true,
+			_ => false,
+		}
+	}
+	pub fn is_failure(&self) -> bool {
+		match self {
+			Self::Error(_) | Self::Critical(_) => true,
+			_ => false,
+		}
+	}
 	pub fn discard_value(self) -> Output<()> {
 		match self {
 			Self::Ok(_) => Output::Ok(()),
@@ -55,37 +169,122 @@ impl<R> Output<R> {
 		}
 	}
 }
+
+///
+/// Failure carried by `?` between outputs of different result types, discarding partial results
+///
+#[derive(Debug, Clone)]
+pub enum Failure {
+	Error(ParseError),
+	Critical(ParseError),
+}
 impl<R> Try for Output<R> {
 	type Ok = R;
-	type Error = Self;
-	fn into_result(self) -> Result<R, Output<R>> {
+	type Error = Failure;
+	fn into_result(self) -> Result<R, Failure> {
 		match self {
 			Self::Ok(r) => Result::Ok(r),
-			_ => Result::Err(self),
+			Self::Partial { error, .. } | Self::Error(error) => Result::Err(Failure::Error(error)),
+			Self::Critical(error) => Result::Err(Failure::Critical(error)),
 		}
 	}
 	fn from_ok(v: R) -> Self {
 		Self::Ok(v)
 	}
-	fn from_error(v: Self) -> Self {
-		v
+	fn from_error(v: Failure) -> Self {
+		match v {
+			Failure::Error(error) => Self::Error(error),
+			Failure::Critical(error) => Self::Critical(error),
+		}
 	}
 }
-impl<R> From<NoneError> for Output<R> {
+impl From<NoneError> for Failure {
 	fn from(_: NoneError) -> Self {
 		Self::Critical(ParseError::Unspecified)
 	}
 }
-// impl<T> From<Output<T>> for Output<()> where  {
-// }
-// impl<F, R> From<Output<F>> for Output<R> {
-// }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct InputContext {
 	pub indent: usize,
 	pub line: usize,
 	pub position: usize,
+	pub offset: usize,
+}
+impl InputContext {
+	pub fn location(&self) -> Location {
+		Location {
+			offset: self.offset,
+			line: self.line,
+			column: self.position,
+		}
+	}
+	pub fn at(self, location: Location) -> Self {
+		Self {
+			line: location.line,
+			position: location.column,
+			offset: location.offset,
+			..self
+		}
+	}
+}
+
+///
+/// Move the input context past a consumed item
+///
+pub trait Advance {
+	fn advance(&self, context: &mut InputContext);
+}
+impl Advance for str {
+	fn advance(&self, context: &mut InputContext) {
+		if is_newline(self) {
+			context.line += 1;
+			context.position = 0;
+		} else {
+			context.position += 1;
+		}
+		context.offset += self.len();
+	}
+}
+impl Advance for String {
+	fn advance(&self, context: &mut InputContext) {
+		self.as_str().advance(context)
+	}
+}
+impl<T: Advance + ?Sized> Advance for &T {
+	fn advance(&self, context: &mut InputContext) {
+		(**self).advance(context)
+	}
+}
+
+///
+/// Position in the source: byte offset, line and grapheme column
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Location {
+	pub offset: usize,
+	pub line: usize,
+	pub column: usize,
+}
+
+///
+/// Source range of a parsed node, end exclusive
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+	pub start: Location,
+	pub end: Location,
+}
+impl Span {
+	pub fn new(start: Location, end: Location) -> Self {
+		Self { start, end }
+	}
+	pub fn to(self, other: Span) -> Self {
+		Self {
+			start: self.start,
+			end: other.end,
+		}
+	}
 }
 
 #[derive(Debug, Clone)]
@@ -102,6 +301,7 @@ impl<T: InputIterItem, I: InputIter<T>> Input<T, I> {
 				indent: 0,
 				line: 0,
 				position: 0,
+				offset: 0,
 			},
 			item: PhantomData,
 		}
@@ -112,13 +312,8 @@ impl<T: InputIterItem, I: InputIter<T>> Input<T, I> {
 				context: self.context,
 				expected: format!("1 more character"),
 			}),
-			Some(x) if is_newline(x.as_ref()) => {
-				self.context.line += 1;
-				self.context.position = 0;
-				Output::Ok(x)
-			}
 			Some(x) => {
-				self.context.position += 1;
+				x.advance(&mut self.context);
 				Output::Ok(x)
 			}
 		}
@@ -133,13 +328,8 @@ impl<T: InputIterItem, I: InputIter<T>> Input<T, I> {
 						expected: format!("{} more characters", by - i),
 					})
 				}
-				Some(x) if is_newline(x.as_ref()) => {
-					self.context.line += 1;
-					self.context.position = 0;
-					result.push_str(x.as_ref());
-				}
 				Some(x) => {
-					self.context.position += 1;
+					x.advance(&mut self.context);
 					result.push_str(x.as_ref());
 				}
 			}
@@ -151,3 +341,57 @@ impl<T: InputIterItem, I: InputIter<T>> Input<T, I> {
 pub type InputRef<'a, T, I> = &'a mut Input<T, I>;
 
 pub trait ParseFn<T: InputIterItem, I: InputIter<T>, R> = Fn(InputRef<T, I>) -> Output<R>;
+
+#[cfg(test)]
+mod tests {
+	use unicode_segmentation::UnicodeSegmentation;
+
+	use super::*;
+
+	#[test]
+	fn locations() {
+		let source = "aé\n🙂b";
+		let mut input = Input::new(source.graphemes(true));
+		let mut locations = Vec::new();
+		while let Output::Ok(_) = input.next() {
+			locations.push(input.context.location());
+		}
+		let location = |offset, line, column| Location {
+			offset,
+			line,
+			column,
+		};
+		assert_eq!(
+			locations,
+			vec![
+				location(1, 0, 1),
+				location(3, 0, 2),
+				location(4, 1, 0),
+				location(8, 1, 1),
+				location(9, 1, 2),
+			]
+		);
+		match input.next() {
+			Output::Critical(ParseError::UnexpectedEOF { context, .. }) => {
+				assert_eq!(context.location(), location(9, 1, 2))
+			}
+			output => panic!("read {:?}", output),
+		}
+	}
+
+	#[test]
+	fn context_at() {
+		let context = InputContext {
+			indent: 2,
+			..InputContext::default()
+		};
+		let location = Location {
+			offset: 12,
+			line: 3,
+			column: 4,
+		};
+		let moved = context.at(location);
+		assert_eq!(moved.location(), location);
+		assert_eq!(moved.indent, 2);
+	}
+}
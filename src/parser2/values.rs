@@ -67,3 +67,55 @@ pub fn is_tab(token: impl InputIterItem) -> bool {
 pub fn is_regular(token: impl InputIterItem) -> bool {
 	is_valid(&token) && !is_special(&token) && !is_newline(&token)
 }
+
+///
+/// Detect ASCII digits
+///
+pub fn is_digit(token: impl InputIterItem) -> bool {
+	token.as_ref().len() == 1 && token.as_ref().as_bytes()[0].is_ascii_digit()
+}
+
+///
+/// Detect number signs
+///
+pub fn is_sign(token: impl InputIterItem) -> bool {
+	["+", "-"].contains(&token.as_ref())
+}
+
+///
+/// Detect brackets and braces
+///
+pub fn is_delimiter(token: impl InputIterItem) -> bool {
+	["(", ")", "[", "]", "{", "}"].contains(&token.as_ref())
+}
+
+///
+/// Detect characters that can start an identifier
+///
+pub fn is_identifier_start(token: impl InputIterItem) -> bool {
+	(is_regular(&token) && !is_digit(&token) && !is_tab(&token)) || token.as_ref() == "_"
+}
+
+///
+/// Detect characters that can continue an identifier
+///
+pub fn is_identifier_char(token: impl InputIterItem) -> bool {
+	(is_regular(&token) && !is_tab(&token)) || token.as_ref() == "_"
+}
+
+///
+/// Detect characters that form operators
+///
+pub fn is_operator_char(token: impl InputIterItem) -> bool {
+	is_special(&token)
+		&& !is_space(&token)
+		&& !is_delimiter(&token)
+		&& !["\"", "#", "_"].contains(&token.as_ref())
+}
+
+///
+/// Operators spanning multiple characters, longest first
+///
+pub const OPERATORS: &[&str] = &[
+	"...", "::", ":=", ":!", ":+", ":-", "=:", "=?", "<-", "->", "=>", "|>", "<|", "..",
+];